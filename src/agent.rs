@@ -1,159 +1,519 @@
-use std::{collections::HashMap, cmp::Reverse};
+use std::{cell::Cell, collections::{HashMap, VecDeque}, cmp::Reverse, fmt, str::FromStr, sync::{Arc, atomic::{AtomicBool, Ordering}}, time::{Duration, Instant}};
 use chronometer::Chronometer;
 use priority_queue::PriorityQueue;
-use crate::puzzle::*;
+use crate::{puzzle::*, error::SearchError, pdb::PatternDatabase, packed::PackedBoard, observer::{SearchObserver, SilentObserver}};
 use slab_tree::*;
 
+mod anytime;
+mod bidirectional;
+mod breadth_first;
+mod depth_first;
+mod snapshot;
+
 #[derive(Clone)]
 pub struct Path {
-    state: Puzzle,
-    _action: ActionType,
-    path_cost: u32,
+    state: PackedBoard,
+    action: ActionType,
+    g: u32, // Moves taken to reach this state.
+    h: u32, // Heuristic estimate of the moves left.
 }
 impl Path {
-    pub fn new(state: Puzzle, action: ActionType, path_cost: u32) -> Path {
-        Path { state, _action: action, path_cost }
+    pub fn new(state: Puzzle, action: ActionType, g: u32, h: u32) -> Path {
+        Path { state: state.pack(), action, g, h }
+    }
+
+    /// Estimated total cost of a solution through this node.
+    pub fn f(&self) -> u32 {
+        self.g + self.h
+    }
+
+    /// The frontier priority of this node under the given strategy.
+    pub fn priority(&self, search_strategy: SearchStrategy) -> u32 {
+        match search_strategy {
+            SearchStrategy::WeightedAStar(weight) => weighted_priority(self.g, self.h, weight),
+            SearchStrategy::BestFirst => self.h,
+            SearchStrategy::AStar | SearchStrategy::IDAStar | SearchStrategy::BidirectionalMM => self.f(),
+            SearchStrategy::BreadthFirst | SearchStrategy::DepthLimited(_) | SearchStrategy::UniformCost |
+            SearchStrategy::Bidirectional => self.g,
+        }
     }
 }
 
+#[derive(Clone)]
 pub struct Solution {
+    /// Every state from the start to the goal.
     pub state_path: Vec<Puzzle>,
+    /// The moves from the start to the goal, in the order to play them.
+    pub actions: Vec<ActionType>,
+    /// The number of moves.
     pub steps: u32,
+    pub certificate: Option<Certificate>,
+    /// What the search that found this solution cost.
+    pub stats: SearchStats,
 }
 impl Solution {
     pub fn print(&self) {
         println!("Solution: ");
         for puzzle in &self.state_path {
-            println!("{}", puzzle.to_string());
+            println!("{}", puzzle);
         }
+        println!("Moves: {}", self.moves());
         println!("Steps: {}\n", self.steps);
     }
+
+    /// Replays the moves from `initial` and checks each one is a real slide that
+    /// reaches the next recorded state, ending at `goal`.
+    pub fn verify(&self, initial: &Puzzle, goal: &Puzzle) -> bool {
+        if self.state_path.first() != Some(initial) || self.state_path.last() != Some(goal) {
+            return false;
+        }
+        if self.actions.len() != self.steps as usize || self.state_path.len() != self.actions.len() + 1 {
+            return false;
+        }
+        self.state_path.windows(2).zip(&self.actions).all(|(pair, action)| {
+            let next = pair[0].act(*action);
+            next != pair[0] && next == pair[1]
+        })
+    }
+
+    /// The moves as one letter each, e.g. "UULDR".
+    pub fn moves(&self) -> String {
+        self.actions.iter().map(|action| action.to_char()).collect()
+    }
+
+    /// True if the search proved no shorter solution exists.
+    pub fn is_optimal(&self) -> bool {
+        match self.certificate {
+            Some(certificate) => certificate.lower_bound >= self.steps,
+            None => false,
+        }
+    }
+
+    /// How many times longer than optimal this solution can be, if the search bounded it.
+    pub fn suboptimality(&self) -> Option<f32> {
+        let certificate = self.certificate?;
+        match certificate.lower_bound {
+            0 => (self.steps == 0).then_some(1.0),
+            lower_bound => Some((self.steps as f32 / lower_bound as f32).max(1.0)),
+        }
+    }
+}
+
+/// A proven lower bound on the solution length, produced by `Agent::solve_optimal`
+/// and `Agent::anytime_search`. The solution is optimal once the bound reaches its steps.
+#[derive(Clone, Copy, Debug)]
+pub struct Certificate {
+    pub heuristic: Heuristic,
+    /// The highest f = g + h popped from the frontier when the search ended.
+    /// Every unexplored path is at least this long.
+    pub lower_bound: u32,
+}
+
+/// Counters from one search, for comparing strategies.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    /// Nodes whose successors were generated.
+    pub expanded: u32,
+    /// Successor states created, including those thrown away as duplicates.
+    pub generated: u32,
+    /// Successors dropped because their state was already reached at no greater cost.
+    pub duplicates: u32,
+    /// Explored states put back in the frontier after a cheaper path turned up.
+    pub reopened: u32,
+    /// Most states waiting to be expanded at once.
+    pub peak_frontier: usize,
+    /// Most reached or expanded states remembered at once.
+    pub peak_explored: usize,
+    /// Calls to the heuristic.
+    pub heuristic_evaluations: u32,
+    /// Wall-clock time of the search.
+    pub elapsed: Duration,
+}
+impl SearchStats {
+    /// The branching factor b of a uniform tree of the given depth holding as many
+    /// nodes as were generated, from generated = b + b^2 + ... + b^depth.
+    pub fn effective_branching_factor(&self, depth: u32) -> f64 {
+        if depth == 0 || self.generated == 0 {
+            return 0.0;
+        }
+        let target = self.generated as f64;
+        let nodes = |b: f64| (1..=depth).map(|i| b.powi(i as i32)).sum::<f64>();
+        let (mut low, mut high) = (0.0, target.max(1.0));
+        for _ in 0..64 {
+            let middle = (low + high) / 2.0;
+            match nodes(middle) < target {
+                true => low = middle,
+                false => high = middle,
+            }
+        }
+        (low + high) / 2.0
+    }
+
+    fn record_peaks(&mut self, frontier: usize, explored: usize) {
+        self.peak_frontier = self.peak_frontier.max(frontier);
+        self.peak_explored = self.peak_explored.max(explored);
+    }
+}
+
+/// Limits on a search besides its node budget, checked before every expansion.
+/// A search that hits one fails with the matching `SearchError`, and
+/// `Agent::best_partial` then gives the most promising path it had found.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    /// Wall-clock time allowed for each search.
+    pub time: Option<Duration>,
+    /// Most states the search may hold in memory at once.
+    pub max_nodes: Option<usize>,
+    /// Set to true from another thread to stop the search.
+    pub cancel: Option<Arc<AtomicBool>>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SearchStrategy {
     BestFirst,
     AStar,
+    IDAStar,
+    /// Uninformed, expands states level by level.
+    BreadthFirst,
+    /// Uninformed depth-first search that gives up below the given depth.
+    DepthLimited(u32),
+    /// Uninformed, orders the frontier by moves taken (h = 0).
+    UniformCost,
+    /// Breadth-first from both the start and the goal, meeting in the middle.
+    Bidirectional,
+    /// Heuristic search from both ends (MM), meeting in the middle.
+    BidirectionalMM,
+    /// A* with f = g + w * h. Solutions are at most w times longer than optimal.
+    /// The weight is applied to two decimal places.
+    WeightedAStar(f32),
+}
+impl SearchStrategy {
+    /// True if the strategy makes use of a heuristic.
+    pub fn is_informed(&self) -> bool {
+        match self {
+            SearchStrategy::BestFirst | SearchStrategy::AStar | SearchStrategy::IDAStar |
+            SearchStrategy::BidirectionalMM | SearchStrategy::WeightedAStar(_) => true,
+            SearchStrategy::BreadthFirst | SearchStrategy::DepthLimited(_) | SearchStrategy::UniformCost |
+            SearchStrategy::Bidirectional => false,
+        }
+    }
+    /// True if a search with this strategy that stops early can be carried on by the
+    /// next call. The others start over. The depth-first strategies keep only the
+    /// current path, so a slice smaller than one pass would never get past it.
+    pub fn is_resumable(&self) -> bool {
+        !matches!(self, SearchStrategy::IDAStar | SearchStrategy::DepthLimited(_) |
+            SearchStrategy::Bidirectional | SearchStrategy::BidirectionalMM)
+    }
+    /// True if the strategy promises a shortest path, so an explored state has to be
+    /// reopened when a cheaper way to it turns up. With a consistent heuristic that never happens.
+    pub fn reopens(&self) -> bool {
+        matches!(self, SearchStrategy::AStar | SearchStrategy::UniformCost)
+    }
 }
-impl ToString for SearchStrategy {
-    fn to_string(&self) -> String {
+impl fmt::Display for SearchStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            SearchStrategy::BestFirst => "Best First",
-            SearchStrategy::AStar => "AStar",
-        }.to_string()
+            SearchStrategy::BestFirst => f.write_str("Best First"),
+            SearchStrategy::AStar => f.write_str("AStar"),
+            SearchStrategy::IDAStar => f.write_str("IDAStar"),
+            SearchStrategy::BreadthFirst => f.write_str("Breadth First"),
+            SearchStrategy::DepthLimited(limit) => write!(f, "Depth Limited ({})", limit),
+            SearchStrategy::UniformCost => f.write_str("Uniform Cost"),
+            SearchStrategy::Bidirectional => f.write_str("Bidirectional"),
+            SearchStrategy::BidirectionalMM => f.write_str("Bidirectional MM"),
+            SearchStrategy::WeightedAStar(weight) => write!(f, "Weighted AStar ({})", weight),
+        }
     }
 }
+impl FromStr for SearchStrategy {
+    type Err = String;
 
-#[derive(Clone, Copy)]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "best-first" | "bestfirst" | "best" => Ok(SearchStrategy::BestFirst),
+            "astar" | "a*" => Ok(SearchStrategy::AStar),
+            "idastar" | "ida*" => Ok(SearchStrategy::IDAStar),
+            "breadth-first" | "bfs" => Ok(SearchStrategy::BreadthFirst),
+            "uniform-cost" | "ucs" => Ok(SearchStrategy::UniformCost),
+            "bidirectional" => Ok(SearchStrategy::Bidirectional),
+            "bidirectional-mm" | "mm" => Ok(SearchStrategy::BidirectionalMM),
+            other if other.starts_with("depth-limited:") => other["depth-limited:".len()..].parse()
+                .map(SearchStrategy::DepthLimited)
+                .map_err(|_| format!("Invalid depth limit in '{}'.", s)),
+            other if other.starts_with("weighted-astar:") => match other["weighted-astar:".len()..].parse() {
+                Ok(weight) if validate_weight(weight).is_ok() => Ok(SearchStrategy::WeightedAStar(weight)),
+                _ => Err(format!("Invalid weight in '{}', expected a number of at least 1.", s)),
+            },
+            _ => Err(format!("Unknown search strategy '{}'.", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Heuristic {
     Misplaced,
     OrthoDistance,
     Inversions,
+    /// Orthogonal distance plus a penalty for tiles blocking each other in a line.
+    LinearConflict,
+    /// The agent's pattern database, see `Agent::set_pattern_database`. Searches fail
+    /// with `SearchError::MissingPatternDatabase` when it wasn't built for the goal.
+    PatternDatabase,
 }
-impl ToString for Heuristic {
-    fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for Heuristic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
             Heuristic::Misplaced => "Misplaced",
             Heuristic::OrthoDistance => "Orthogonal Distance",
             Heuristic::Inversions => "Inversions",
-        }.to_string()
+            Heuristic::LinearConflict => "Linear Conflict",
+            Heuristic::PatternDatabase => "Pattern Database",
+        })
+    }
+}
+impl Heuristic {
+    /// True if the heuristic never overestimates the moves left.
+    pub fn is_admissible(&self) -> bool {
+        match self {
+            Heuristic::Misplaced | Heuristic::OrthoDistance | Heuristic::LinearConflict |
+            Heuristic::PatternDatabase => true,
+            Heuristic::Inversions => false,
+        }
+    }
+}
+impl FromStr for Heuristic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "misplaced" => Ok(Heuristic::Misplaced),
+            "manhattan" | "ortho" | "orthodistance" => Ok(Heuristic::OrthoDistance),
+            "inversions" => Ok(Heuristic::Inversions),
+            "linear-conflict" | "linearconflict" => Ok(Heuristic::LinearConflict),
+            "pdb" | "pattern-database" => Ok(Heuristic::PatternDatabase),
+            _ => Err(format!("Unknown heuristic '{}'.", s)),
+        }
     }
 }
 
 pub struct Agent {
     tree: Tree<Path>,
     frontier_prique: PriorityQueue<NodeId, Reverse<u32>>,
-    frontier_hmap: HashMap<Puzzle, NodeId>,
-    explored_hmap: HashMap<Puzzle, NodeId>,
+    frontier_hmap: HashMap<PackedBoard, NodeId>,
+    explored_hmap: HashMap<PackedBoard, NodeId>,
+    queue: VecDeque<NodeId>, // Breadth-first search's frontier, in order.
     goal: Puzzle,
+    goal_key: PackedBoard, // The goal packed, to compare against tree nodes.
+    lower_bound: u32, // Highest priority popped so far; a bound on the solution length under A*.
+    stats: SearchStats, // Counters for the last search.
+    evaluations: Cell<u32>, // Heuristic calls, counted where only `&self` is available.
+    pattern_database: Option<Arc<PatternDatabase>>,
+    observer: Box<dyn SearchObserver>,
+    limits: SearchLimits,
+    started: Instant, // When the current search began, for the time limit.
+    stopped: Option<SearchError>, // The budget or limit that ended the last search, if any.
+    partial: Option<Solution>, // Best path kept by a depth-first search that ended early.
+    session: Option<Session>, // Set while the last search can be carried on.
+}
+
+/// The settings of a search that stopped early, which a call with the same
+/// settings picks up from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Session {
+    strategy: SearchStrategy,
+    heuristic: Heuristic,
 }
 impl Agent {
-    pub fn new(initial: Puzzle, goal: Puzzle) -> Agent {
+    /// Creates an agent for the given boards, rejecting pairs that can never be solved.
+    pub fn new(initial: Puzzle, goal: Puzzle) -> Result<Agent, SearchError> {
+        validate(&initial, &goal)?;
+
         // Create root node, then tree.
-        let path_cost = 0;
-        let root = Path::new(initial.clone(), ActionType::None, path_cost);
+        let root = Path::new(initial, ActionType::None, 0, 0);
         let tree = TreeBuilder::<Path>::new().with_root(root).build();
-        let root_id = tree.root_id().unwrap();
-        
-        let mut frontier = PriorityQueue::<NodeId, Reverse<u32>>::new();
-        let mut frontier_hash = HashMap::new();
-        let explored = HashMap::new();
+        let goal_key = goal.pack();
+
+        let mut agent = Agent { tree, frontier_prique: PriorityQueue::new(), frontier_hmap: HashMap::new(), explored_hmap: HashMap::new(), queue: VecDeque::new(),
+            goal, goal_key, lower_bound: 0, stats: SearchStats::default(), evaluations: Cell::new(0), pattern_database: None,
+            observer: Box::new(SilentObserver), limits: SearchLimits::default(), started: Instant::now(), stopped: None, partial: None,
+            session: None };
+        agent.restart();
+        Ok(agent)
+    }
 
-        // Add the root node to the frontier.
-        frontier.push(root_id, Reverse(path_cost));
-        frontier_hash.insert(initial, root_id);
+    /// Supplies the tables used by `Heuristic::PatternDatabase`.
+    pub fn set_pattern_database(&mut self, database: Arc<PatternDatabase>) {
+        self.pattern_database = Some(database);
+    }
 
-        Agent { tree, frontier_prique: frontier, frontier_hmap: frontier_hash, explored_hmap: explored, goal }
+    /// Replaces the observer told about each search's progress, `SilentObserver` by default.
+    pub fn set_observer(&mut self, observer: Box<dyn SearchObserver>) {
+        self.observer = observer;
     }
 
-    pub fn uniform_cost_search(&mut self, search_strategy: SearchStrategy, heuristic: Heuristic, loop_count: u32) -> Option<Solution> {
+    /// Sets the time, memory and cancellation limits for later searches.
+    pub fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+    }
+
+    /// Why the last search stopped before it was done: `BudgetExhausted` or the limit
+    /// that was hit. `None` if it ran to completion.
+    pub fn stop_reason(&self) -> Option<SearchError> {
+        self.stopped
+    }
+
+    /// When the last search stopped early, the path to the state it reached that looked
+    /// closest to the goal by Manhattan distance. Worked out on each call, so stopping
+    /// and resuming a search stays cheap. The bidirectional strategies don't keep a
+    /// search tree, so for them this is just the start.
+    pub fn best_partial(&self) -> Option<Solution> {
+        self.stopped?;
+        match &self.partial {
+            Some(partial) => Some(partial.clone()),
+            None => self.closest_reached(),
+        }
+    }
+
+    /// Counters for the last search, whether or not it found the goal.
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    /// Runs A* with an admissible heuristic and certifies the returned solution as minimal.
+    pub fn solve_optimal(&mut self, heuristic: Heuristic, loop_count: u32) -> Result<Solution, SearchError> {
+        if !heuristic.is_admissible() {
+            return Err(SearchError::InadmissibleHeuristic(heuristic));
+        }
+        let mut solution = self.uniform_cost_search(SearchStrategy::AStar, heuristic, loop_count)?;
+        solution.certificate = Some(Certificate { heuristic, lower_bound: self.lower_bound });
+
+        Ok(solution)
+    }
+
+    /// Searches for the goal with the given strategy, expanding at most `loop_count` nodes.
+    /// If the last call used the same strategy and heuristic and stopped on its budget or
+    /// a limit, this one carries on from where it stopped, adding to its `SearchStats`.
+    pub fn uniform_cost_search(&mut self, search_strategy: SearchStrategy, heuristic: Heuristic, loop_count: u32) -> Result<Solution, SearchError> {
+        if let SearchStrategy::WeightedAStar(weight) = search_strategy {
+            validate_weight(weight)?;
+        }
+        self.check_heuristic(search_strategy, heuristic)?;
         let mut watch = Chronometer::new();
         let mut counter = loop_count;
         
         watch.start();
-        self.tree.root().unwrap().data().state.print("Initial");
+        let session = Session { strategy: search_strategy, heuristic };
+        if self.session.take() != Some(session) {
+            self.restart();
+        }
+        let previous = self.stats;
+        self.start_search();
+        if let Some(initial) = self.initial() {
+            self.observer.on_start(&initial);
+        }
+
+        let solution = match search_strategy {
+            SearchStrategy::IDAStar => self.iterative_deepening(heuristic, &mut counter),
+            SearchStrategy::DepthLimited(limit) => self.depth_limited(limit, &mut counter),
+            SearchStrategy::BreadthFirst => self.breadth_first(&mut counter),
+            SearchStrategy::Bidirectional => self.bidirectional_breadth_first(&mut counter),
+            SearchStrategy::BidirectionalMM => self.bidirectional_heuristic(heuristic, &mut counter),
+            _ => self.graph_search(search_strategy, heuristic, &mut counter),
+        };
+        let elapsed = previous.elapsed + watch.duration().unwrap_or_default();
+        let stats = self.finish_stats(previous.expanded + (loop_count - counter), elapsed);
+        let resumable = search_strategy.is_resumable().then_some(session);
 
-        while counter > 0 {
-            counter -= 1;
+        let result = match (solution, counter, self.stopped) {
+            (Some(mut solution), _, _) => {
+                solution.stats = stats;
+                self.observer.on_goal(&solution);
+                Ok(solution)
+            },
+            (None, _, Some(error)) => {
+                self.session = resumable;
+                Err(error)
+            },
+            (None, 0, None) => {
+                self.stopped = Some(SearchError::BudgetExhausted);
+                self.session = resumable;
+                self.observer.on_budget_exhausted(&stats);
+                Err(SearchError::BudgetExhausted)
+            },
+            (None, _, None) => Err(SearchError::NoSolution),
+        };
+        self.observer.on_finish(&stats);
+        result
+    }
+
+    /// Best-first graph search over the tree, ordered by the strategy's priority.
+    fn graph_search(&mut self, search_strategy: SearchStrategy, heuristic: Heuristic, counter: &mut u32) -> Option<Solution> {
+        while *counter > 0 {
+            if self.limit_reached(self.frontier_prique.len() + self.explored_hmap.len()) {
+                return None;
+            }
             // Check if the frontier is empty.
             // Returns no solution if true, the cheapest path cost node if false.
             let parent_id = match self.frontier_prique.pop() {
-                    Some(t) => t.0,
-                    None => {
-                        timer(counter, loop_count, &watch);
-                        return None
+                    Some((id, Reverse(priority))) => {
+                        self.lower_bound = self.lower_bound.max(priority);
+                        id
                     },
+                    None => return None,
             };
             let parent = self.tree.get(parent_id)?.data().clone();
             self.frontier_hmap.remove(&parent.state);
                 
             // If the goal state has been reached then return the solution.
-            if parent.state == self.goal {
-                timer(counter, loop_count, &watch);
+            if parent.state == self.goal_key {
                 return self.solution(parent_id);
             }
+            *counter -= 1;
                 
             // Add the node's state to explored to show we've now reached that state.
             self.explored_hmap.insert(parent.state.clone(), parent_id);
-                
             
-            // Iterate through all action types to add to the frontier.
-            for action in [ActionType::Up, ActionType::Down, ActionType::Left, ActionType::Right].iter() {
-                // First, create a new child node.
-                let state = parent.state.act(*action);
-                let h = get_heuristic(&state, &self.goal, heuristic);
-                let g = 1;
-                let path_cost = parent.path_cost + 
-                    match search_strategy {
-                        SearchStrategy::BestFirst => h,
-                        SearchStrategy::AStar => h + g,
-                    };
-                let child = Path::new(state, *action, path_cost);
-
-                // Search to see if new child's state is already in the frontier or explored.
-                let child_in_frontier = self.frontier_hmap.contains_key(&child.state);
-                let child_in_explored = self.explored_hmap.contains_key(&child.state);
-
-                if !child_in_explored || !child_in_frontier {
-                    // Insert the child node into the frontier since it's not there yet.
-                    self.frontier_insert(parent_id, child);
+            let board = self.unpack(&parent.state);
+            self.observer.on_expand(&board, parent.g);
+            
+            // Iterate through every real move except the one back to the parent's parent.
+            for (action, state) in board.successors(parent.action) {
+                let key = state.pack();
+                let g = parent.g + 1;
+                self.stats.generated += 1;
+                self.observer.on_generate(&state, g);
+
+                // An explored state is only worth another look along a cheaper path, and
+                // only for strategies that promise the shortest one.
+                if let Some(&id) = self.explored_hmap.get(&key) {
+                    if !search_strategy.reopens() || self.tree.get(id)?.data().g <= g {
+                        self.stats.duplicates += 1;
+                        continue;
+                    }
+                    self.explored_hmap.remove(&key);
+                    self.stats.reopened += 1;
                 }
-                // If child's state is in the frontier with a higher path cost then replace it.
-                else if child_in_frontier {
-                    let id = *self.frontier_hmap.get(&child.state)?;
-                    let existing_path_cost = self.tree.get(id)?.data().path_cost;
-
-                    if child.path_cost < existing_path_cost {
-                        self.frontier_remove(id, &child.state);
-                        self.frontier_insert(parent_id, child);
+                // A state waiting in the frontier is replaced if this path is cheaper.
+                if let Some(&id) = self.frontier_hmap.get(&key) {
+                    if self.tree.get(id)?.data().g <= g {
+                        self.stats.duplicates += 1;
+                        continue;
                     }
+                    self.frontier_remove(id, &key);
                 }
+
+                let h = match search_strategy.is_informed() {
+                    true => self.estimate(&state, &self.goal, heuristic),
+                    false => 0,
+                };
+                self.frontier_insert(parent_id, Path::new(state, action, g, h), search_strategy);
             }
+            self.stats.record_peaks(self.frontier_prique.len(), self.explored_hmap.len());
         }
         None
     }
 
-    fn frontier_insert(&mut self, parent_id: NodeId, child: Path) {
+    fn frontier_insert(&mut self, parent_id: NodeId, child: Path, search_strategy: SearchStrategy) {
         let mut parent = 
         match self.tree.get_mut(parent_id) {
             Some(t) => t,
@@ -162,40 +522,176 @@ impl Agent {
         let mut child_node = parent.append(child);
         let child_id = child_node.node_id();
         let child_data = child_node.data();
-        let priority = Reverse(child_data.path_cost);
+        let priority = Reverse(child_data.priority(search_strategy));
         self.frontier_prique.push(child_id, priority);
         self.frontier_hmap.insert(child_data.state.clone(), child_id);
     }
 
-    fn frontier_remove(&mut self, node_id: NodeId, state: &Puzzle) {
+    fn frontier_remove(&mut self, node_id: NodeId, state: &PackedBoard) {
         self.frontier_prique.remove(&node_id);
         self.frontier_hmap.remove(state);
         self.tree.remove(node_id, RemoveBehavior::DropChildren);
     }
 
+    /// Throws away everything searched so far, leaving only the start in the frontier.
+    fn restart(&mut self) {
+        let Some(root) = self.tree.root() else { return };
+        let root = Path { state: root.data().state.clone(), action: ActionType::None, g: 0, h: 0 };
+        let state = root.state.clone();
+        self.tree = TreeBuilder::<Path>::new().with_root(root).build();
+        let Some(root_id) = self.tree.root_id() else { return };
+
+        self.frontier_prique.clear();
+        self.frontier_prique.push(root_id, Reverse(0));
+        self.frontier_hmap = HashMap::from([(state, root_id)]);
+        self.explored_hmap.clear();
+        self.queue.clear();
+        self.lower_bound = 0;
+        self.session = None;
+        self.stats = SearchStats::default();
+        self.evaluations.set(0);
+    }
+
+    /// Clears what the last call left behind and starts the clock for the time limit.
+    fn start_search(&mut self) {
+        self.started = Instant::now();
+        self.stopped = None;
+        self.partial = None;
+    }
+
+    /// Checks the limits from `set_limits`, given how many states the search holds,
+    /// and records the one that was hit.
+    fn limit_reached(&mut self, in_memory: usize) -> bool {
+        let limits = &self.limits;
+        self.stopped = if limits.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+            Some(SearchError::Cancelled)
+        } else if limits.time.is_some_and(|time| self.started.elapsed() >= time) {
+            Some(SearchError::TimeLimit)
+        } else if limits.max_nodes.is_some_and(|max_nodes| in_memory > max_nodes) {
+            Some(SearchError::MemoryLimit)
+        } else {
+            None
+        };
+        self.stopped.is_some()
+    }
+
+    /// The path to the tree node closest to the goal, fewest moves first on ties.
+    fn closest_reached(&self) -> Option<Solution> {
+        let (_, id) = self.explored_hmap.iter().chain(&self.frontier_hmap)
+            .map(|(state, id)| {
+                let distance = self.unpack(state).heuristic_distances(&self.goal);
+                ((distance, self.tree.get(*id).map_or(0, |node| node.data().g)), *id)
+            })
+            .min_by_key(|(key, _)| *key)?;
+        self.solution(id)
+    }
+
+    /// Fills in the counters kept outside `stats` and returns a copy.
+    fn finish_stats(&mut self, expanded: u32, elapsed: Duration) -> SearchStats {
+        self.stats.expanded = expanded;
+        self.stats.heuristic_evaluations = self.evaluations.get();
+        self.stats.elapsed = elapsed;
+        self.stats
+    }
+
+    /// The board the search starts from.
+    fn initial(&self) -> Option<Puzzle> {
+        Some(self.unpack(&self.tree.root()?.data().state))
+    }
+
+    fn unpack(&self, state: &PackedBoard) -> Puzzle {
+        state.unpack_trusted(self.goal.dimension())
+    }
+
+    /// Fails if the strategy would use the pattern database heuristic without a database
+    /// built for every board it estimates towards.
+    fn check_heuristic(&self, search_strategy: SearchStrategy, heuristic: Heuristic) -> Result<(), SearchError> {
+        if heuristic != Heuristic::PatternDatabase || !search_strategy.is_informed() {
+            return Ok(());
+        }
+        let matches = self.pattern_database.as_ref().is_some_and(|database| database.goal() == &self.goal);
+        // MM's backward side estimates towards the start, which no database covers.
+        match matches && search_strategy != SearchStrategy::BidirectionalMM {
+            true => Ok(()),
+            false => Err(SearchError::MissingPatternDatabase),
+        }
+    }
+
+    /// Heuristic estimate of the moves from `state` to `target`.
+    fn estimate(&self, state: &Puzzle, target: &Puzzle, heuristic: Heuristic) -> u32 {
+        self.evaluations.set(self.evaluations.get() + 1);
+        if let (Heuristic::PatternDatabase, Some(database)) = (heuristic, &self.pattern_database) {
+            if database.goal() == target {
+                return database.heuristic(state);
+            }
+        }
+        get_heuristic(state, target, heuristic)
+    }
+
     fn solution(&self, start: NodeId) -> Option<Solution> {
         let node = self.tree.get(start)?;
         let mut steps = 0;
-        let mut state_path = vec![node.data().state.clone()];
+        let mut state_path = vec![self.unpack(&node.data().state)];
+        let mut actions = vec![node.data().action];
 
         for parent in node.ancestors() {
-            state_path.push(parent.data().state.clone());
+            state_path.push(self.unpack(&parent.data().state));
+            actions.push(parent.data().action);
             steps += 1;
         }
+        // The root has no move leading to it.
+        actions.pop();
+        actions.reverse();
+        state_path.reverse();
 
-        Some(Solution { state_path, steps })
+        Some(Solution { state_path, actions, steps, certificate: None, stats: SearchStats::default() })
     }
 
 }
 
+/// Checks the boards have the same shape and that the goal is reachable. Every
+/// `Puzzle` constructor checks its tiles are each of 0..rows*columns once, so two
+/// boards of the same shape always hold the same tiles.
+fn validate(initial: &Puzzle, goal: &Puzzle) -> Result<(), SearchError> {
+    if initial.dimension() != goal.dimension() {
+        return Err(SearchError::DimensionMismatch);
+    }
+    if !initial.is_solvable_to(goal) {
+        return Err(SearchError::Unsolvable);
+    }
+    Ok(())
+}
+
+/// Rejects weights that would make weighted A* meaningless.
+fn validate_weight(weight: f32) -> Result<(), SearchError> {
+    match weight.is_finite() && weight >= 1.0 {
+        true => Ok(()),
+        false => Err(SearchError::InvalidWeight(weight)),
+    }
+}
+
+/// Weighted f = g + w * h in hundredths, so priorities stay integers.
+/// Saturates rather than overflowing on long paths or large weights.
+fn weighted_priority(g: u32, h: u32, weight: f32) -> u32 {
+    let priority = ((weight * 100.0).round() as u64).saturating_mul(h as u64).saturating_add(g as u64 * 100);
+    priority.min(u32::MAX as u64) as u32
+}
+
+/// Builds a solution from a start-to-goal list of states.
+fn path_solution(path: Vec<Puzzle>) -> Solution {
+    let steps = path.len() as u32 - 1;
+    let actions = path.windows(2)
+        .map(|pair| pair[0].action_to(&pair[1]).unwrap_or(ActionType::None))
+        .collect();
+    Solution { state_path: path, actions, steps, certificate: None, stats: SearchStats::default() }
+}
+
 fn get_heuristic(state: &Puzzle, goal: &Puzzle, heuristic: Heuristic) -> u32 {
     match heuristic {
-        Heuristic::Misplaced => state.heuristic_misplaced(goal) as u32,
+        Heuristic::Misplaced => state.heuristic_misplaced(goal),
         Heuristic::OrthoDistance => state.heuristic_distances(goal),
         Heuristic::Inversions => state.inversions(),
+        Heuristic::LinearConflict => state.heuristic_linear_conflict(goal),
+        Heuristic::PatternDatabase => state.heuristic_distances(goal),
     }
 }
-
-fn timer(counter: u32, loop_count: u32, watch: &Chronometer) {
-    println!("{:6} {:.6} s", loop_count - counter, watch.duration().unwrap().as_secs_f32());
-}
@@ -0,0 +1,113 @@
+use super::*;
+use super::bidirectional::{Reached, Side};
+
+impl Agent {
+    /// Anytime weighted A* (AWA*). Runs weighted A* with the given weight and keeps
+    /// searching after the first solution, pruning anything that can't beat the best
+    /// one so far. Every improvement is returned in order, each certified with the
+    /// lower bound known when it was found. If the frontier empties before the
+    /// budget runs out, the last solution is proven optimal, otherwise `Agent::stop_reason`
+    /// says what cut it short. Fails if the weight is below 1 or not finite, if the
+    /// heuristic needs a pattern database it doesn't have, or if the budget or a limit
+    /// runs out before any solution is found.
+    pub fn anytime_search(&mut self, heuristic: Heuristic, weight: f32, loop_count: u32) -> Result<Vec<Solution>, SearchError> {
+        validate_weight(weight)?;
+        self.check_heuristic(SearchStrategy::WeightedAStar(weight), heuristic)?;
+        let mut watch = Chronometer::new();
+        watch.start();
+        self.restart();
+        self.start_search();
+        let mut solutions = Vec::new();
+        let Some(initial) = self.initial() else {
+            return Ok(solutions);
+        };
+        self.observer.on_start(&initial);
+        let goal = self.goal.clone();
+        let certificate = |lower_bound| Some(Certificate { heuristic, lower_bound });
+        if initial == goal {
+            let mut solution = path_solution(vec![initial]);
+            solution.certificate = certificate(0);
+            solution.stats = self.finish_stats(0, watch.duration().unwrap_or_default());
+            self.observer.on_goal(&solution);
+            self.observer.on_finish(&solution.stats);
+            solutions.push(solution);
+            return Ok(solutions);
+        }
+
+        let mut reached = Side::new(initial.pack(), goal.clone());
+        // The weighted priority drives the search, plain f gives the lower bound.
+        let mut frontier = PriorityQueue::new();
+        let mut frontier_f = PriorityQueue::new();
+        let h = self.estimate(&initial, &goal, heuristic);
+        frontier.push(initial.pack(), Reverse(weighted_priority(0, h, weight)));
+        frontier_f.push(initial.pack(), Reverse(h));
+        let mut incumbent = u32::MAX;
+
+        let mut counter = loop_count;
+        while counter > 0 {
+            if self.limit_reached(reached.reached.len()) {
+                break;
+            }
+            let Some((parent, _)) = frontier.pop() else {
+                // Nothing left that could improve on the incumbent.
+                if let Some(last) = solutions.last_mut() {
+                    last.certificate = certificate(incumbent);
+                    self.lower_bound = incumbent;
+                }
+                break;
+            };
+            let Some((_, Reverse(parent_f))) = frontier_f.remove(&parent) else { continue };
+            if parent_f >= incumbent {
+                continue;
+            }
+            counter -= 1;
+            let g = reached.g(&parent).unwrap_or(0) + 1;
+            let board = self.unpack(&parent);
+            self.observer.on_expand(&board, g - 1);
+
+            for (_, child) in board.successors(ActionType::None) {
+                let state = child.pack();
+                self.stats.generated += 1;
+                self.observer.on_generate(&child, g);
+                if reached.g(&state).is_some_and(|existing| existing <= g) {
+                    self.stats.duplicates += 1;
+                    continue;
+                }
+                let h = self.estimate(&child, &goal, heuristic);
+                if g + h >= incumbent {
+                    continue;
+                }
+                reached.reached.insert(state.clone(), Reached { g, parent: Some(parent.clone()) });
+
+                if state == self.goal_key {
+                    incumbent = g;
+                    // The parent is out of the frontier and its later children aren't in yet,
+                    // so it has to stand in for them in the bound.
+                    let open_bound = frontier_f.peek().map_or(incumbent, |(_, Reverse(f))| *f).min(parent_f);
+                    let mut states: Vec<Puzzle> = reached.chain(&state).iter().map(|state| self.unpack(state)).collect();
+                    states.reverse();
+                    let mut solution = path_solution(states);
+                    solution.certificate = certificate(open_bound.min(incumbent));
+                    // The cost of the search up to this solution.
+                    solution.stats = self.finish_stats(loop_count - counter, watch.duration().unwrap_or_default());
+                    self.observer.on_goal(&solution);
+                    solutions.push(solution);
+                    continue;
+                }
+                frontier.push(state.clone(), Reverse(weighted_priority(g, h, weight)));
+                frontier_f.push(state, Reverse(g + h));
+            }
+            self.stats.record_peaks(frontier.len(), reached.reached.len());
+        }
+        let stats = self.finish_stats(loop_count - counter, watch.duration().unwrap_or_default());
+        if self.stopped.is_none() && counter == 0 && !frontier.is_empty() {
+            self.stopped = Some(SearchError::BudgetExhausted);
+            self.observer.on_budget_exhausted(&stats);
+        }
+        self.observer.on_finish(&stats);
+        match solutions.is_empty() {
+            true => Err(self.stopped.unwrap_or(SearchError::NoSolution)),
+            false => Ok(solutions),
+        }
+    }
+}
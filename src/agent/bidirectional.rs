@@ -0,0 +1,176 @@
+use super::*;
+
+/// A state reached by a search that keeps parents in a map instead of the tree.
+pub(super) struct Reached {
+    pub(super) g: u32,
+    pub(super) parent: Option<PackedBoard>,
+}
+
+/// One direction of a bidirectional search, working toward `target`.
+pub(super) struct Side {
+    pub(super) target: Puzzle,
+    pub(super) reached: HashMap<PackedBoard, Reached>,
+}
+impl Side {
+    pub(super) fn new(root: PackedBoard, target: Puzzle) -> Side {
+        let mut reached = HashMap::new();
+        reached.insert(root, Reached { g: 0, parent: None });
+        Side { target, reached }
+    }
+
+    pub(super) fn g(&self, state: &PackedBoard) -> Option<u32> {
+        self.reached.get(state).map(|r| r.g)
+    }
+
+    /// States from `state` back to this side's root, `state` first.
+    pub(super) fn chain(&self, state: &PackedBoard) -> Vec<PackedBoard> {
+        let mut chain = vec![state.clone()];
+        while let Some(parent) = self.reached.get(chain.last().unwrap()).and_then(|r| r.parent.clone()) {
+            chain.push(parent);
+        }
+        chain
+    }
+}
+
+impl Agent {
+    /// Bidirectional breadth-first search. Whole layers are expanded on the
+    /// smaller side so the first layer that meets the other side holds a shortest path.
+    pub(super) fn bidirectional_breadth_first(&mut self, counter: &mut u32) -> Option<Solution> {
+        let initial = self.initial()?;
+        if initial == self.goal {
+            return Some(path_solution(vec![initial]));
+        }
+        let mut forward = Side::new(initial.pack(), self.goal.clone());
+        let mut backward = Side::new(self.goal_key.clone(), initial.clone());
+        let mut forward_layer = vec![initial.pack()];
+        let mut backward_layer = vec![self.goal_key.clone()];
+
+        while !forward_layer.is_empty() && !backward_layer.is_empty() {
+            let forward_turn = forward_layer.len() <= backward_layer.len();
+            let (side, other, layer) = match forward_turn {
+                true => (&mut forward, &backward, &mut forward_layer),
+                false => (&mut backward, &forward, &mut backward_layer),
+            };
+
+            let mut next_layer = Vec::new();
+            let mut meeting: Option<(u32, PackedBoard)> = None;
+            for parent in layer.iter() {
+                if *counter == 0 || self.limit_reached(side.reached.len() + other.reached.len()) {
+                    return None;
+                }
+                *counter -= 1;
+                let g = side.g(parent)? + 1;
+                let board = self.unpack(parent);
+                self.observer.on_expand(&board, g - 1);
+
+                for (_, state) in board.successors(ActionType::None) {
+                    self.stats.generated += 1;
+                    self.observer.on_generate(&state, g);
+                    let state = state.pack();
+                    if side.reached.contains_key(&state) {
+                        self.stats.duplicates += 1;
+                        continue;
+                    }
+                    side.reached.insert(state.clone(), Reached { g, parent: Some(parent.clone()) });
+                    if let Some(other_g) = other.g(&state) {
+                        if meeting.as_ref().is_none_or(|(cost, _)| g + other_g < *cost) {
+                            meeting = Some((g + other_g, state.clone()));
+                        }
+                    }
+                    next_layer.push(state);
+                }
+            }
+            *layer = next_layer;
+            self.stats.record_peaks(forward_layer.len() + backward_layer.len(), forward.reached.len() + backward.reached.len());
+
+            if let Some((cost, state)) = meeting {
+                self.lower_bound = cost;
+                return Some(self.stitch(&forward, &backward, &state));
+            }
+        }
+        None
+    }
+
+    /// Bidirectional heuristic search in the style of MM: each side orders its
+    /// frontier by max(f, 2g), with the backward side estimating distance to the start.
+    /// It stops once no unexpanded pair of nodes could beat the best meeting found.
+    pub(super) fn bidirectional_heuristic(&mut self, heuristic: Heuristic, counter: &mut u32) -> Option<Solution> {
+        let initial = self.initial()?;
+        let mut forward = Side::new(initial.pack(), self.goal.clone());
+        let mut backward = Side::new(self.goal_key.clone(), initial.clone());
+        let mut forward_frontier = PriorityQueue::new();
+        let mut backward_frontier = PriorityQueue::new();
+        forward_frontier.push(initial.pack(), Reverse(mm_priority(0, self.estimate(&initial, &self.goal, heuristic))));
+        backward_frontier.push(self.goal_key.clone(), Reverse(mm_priority(0, self.estimate(&self.goal, &initial, heuristic))));
+
+        // Cheapest meeting found so far.
+        let mut best: Option<(u32, PackedBoard)> = match initial == self.goal {
+            true => Some((0, initial.pack())),
+            false => None,
+        };
+
+        loop {
+            let forward_min = forward_frontier.peek().map(|(_, Reverse(p))| *p);
+            let backward_min = backward_frontier.peek().map(|(_, Reverse(p))| *p);
+            let lowest = match (forward_min, backward_min) {
+                (Some(f), Some(b)) => f.min(b),
+                _ => u32::MAX,
+            };
+            if let Some((cost, state)) = &best {
+                if *cost <= lowest {
+                    self.lower_bound = *cost;
+                    return Some(self.stitch(&forward, &backward, state));
+                }
+            }
+            if lowest == u32::MAX || *counter == 0 || self.limit_reached(forward.reached.len() + backward.reached.len()) {
+                return None;
+            }
+            *counter -= 1;
+
+            let forward_turn = forward_min <= backward_min;
+            let (side, other, frontier) = match forward_turn {
+                true => (&mut forward, &backward, &mut forward_frontier),
+                false => (&mut backward, &forward, &mut backward_frontier),
+            };
+            let (parent, _) = frontier.pop()?;
+            let g = side.g(&parent)? + 1;
+            let board = self.unpack(&parent);
+            self.observer.on_expand(&board, g - 1);
+
+            for (_, child) in board.successors(ActionType::None) {
+                let state = child.pack();
+                self.stats.generated += 1;
+                self.observer.on_generate(&child, g);
+                if side.g(&state).is_some_and(|existing| existing <= g) {
+                    self.stats.duplicates += 1;
+                    continue;
+                }
+                let h = self.estimate(&child, &side.target, heuristic);
+                side.reached.insert(state.clone(), Reached { g, parent: Some(parent.clone()) });
+                frontier.push(state.clone(), Reverse(mm_priority(g, h)));
+
+                if let Some(other_g) = other.g(&state) {
+                    if best.as_ref().is_none_or(|(cost, _)| g + other_g < *cost) {
+                        best = Some((g + other_g, state));
+                    }
+                }
+            }
+            let frontier = forward_frontier.len() + backward_frontier.len();
+            self.stats.record_peaks(frontier, forward.reached.len() + backward.reached.len());
+        }
+    }
+
+    /// Joins the forward half (start to `meeting`) and the backward half (`meeting` to goal).
+    fn stitch(&self, forward: &Side, backward: &Side, meeting: &PackedBoard) -> Solution {
+        let mut states = forward.chain(meeting);
+        states.reverse();
+        states.extend(backward.chain(meeting).into_iter().skip(1));
+        path_solution(states.iter().map(|state| self.unpack(state)).collect())
+    }
+}
+
+/// MM frontier priority, max(g + h, 2g).
+fn mm_priority(g: u32, h: u32) -> u32 {
+    (g + h).max(2 * g)
+}
+
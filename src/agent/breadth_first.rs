@@ -0,0 +1,51 @@
+use super::*;
+
+impl Agent {
+    /// Breadth-first search. States are goal tested as they are generated and
+    /// `explored_hmap` doubles as the set of every state reached so far. The queue
+    /// lives in the agent, so a search that ran out of budget can carry on.
+    pub(super) fn breadth_first(&mut self, counter: &mut u32) -> Option<Solution> {
+        if self.explored_hmap.is_empty() {
+            let root_id = self.tree.root_id()?;
+            let root_state = self.tree.get(root_id)?.data().state.clone();
+            if root_state == self.goal_key {
+                return self.solution(root_id);
+            }
+            self.queue = VecDeque::from([root_id]);
+            self.explored_hmap.insert(root_state, root_id);
+        }
+
+        while *counter > 0 {
+            if self.limit_reached(self.explored_hmap.len()) {
+                return None;
+            }
+            let parent_id = self.queue.pop_front()?;
+            *counter -= 1;
+            let parent = self.tree.get(parent_id)?.data().clone();
+
+            let board = self.unpack(&parent.state);
+            self.observer.on_expand(&board, parent.g);
+
+            for (action, state) in board.successors(parent.action) {
+                let key = state.pack();
+                self.stats.generated += 1;
+                self.observer.on_generate(&state, parent.g + 1);
+                if self.explored_hmap.contains_key(&key) {
+                    self.stats.duplicates += 1;
+                    continue;
+                }
+                let child = Path::new(state, action, parent.g + 1, 0);
+                let child_id = self.tree.get_mut(parent_id)?.append(child).node_id();
+
+                if key == self.goal_key {
+                    self.lower_bound = parent.g + 1;
+                    return self.solution(child_id);
+                }
+                self.explored_hmap.insert(key, child_id);
+                self.queue.push_back(child_id);
+            }
+            self.stats.record_peaks(self.queue.len(), self.explored_hmap.len());
+        }
+        None
+    }
+}
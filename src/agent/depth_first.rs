@@ -0,0 +1,99 @@
+use super::*;
+
+/// Outcome of one depth-first pass bounded by f.
+enum Bound {
+    Found,
+    Next(u32), // Smallest f that exceeded the bound.
+    Exhausted, // Ran out of budget.
+}
+
+impl Agent {
+    /// Iterative deepening A*. Only the current path is kept in memory, so
+    /// the cost is O(depth) no matter how many nodes are expanded.
+    pub(super) fn iterative_deepening(&mut self, heuristic: Heuristic, counter: &mut u32) -> Option<Solution> {
+        let initial = self.initial()?;
+        let mut bound = self.estimate(&initial, &self.goal, heuristic);
+        let mut path = vec![initial];
+
+        loop {
+            match self.bounded_search(&mut path, ActionType::None, 0, bound, Some(heuristic), counter) {
+                Bound::Found => {
+                    self.lower_bound = bound;
+                    return Some(path_solution(path));
+                },
+                // Nothing was pruned, so there is nowhere left to look.
+                Bound::Next(u32::MAX) => return None,
+                Bound::Exhausted => {
+                    self.partial = self.closest_on_path(&path);
+                    return None;
+                },
+                Bound::Next(next) => bound = next,
+            }
+        }
+    }
+
+    /// Plain depth-first search that never goes deeper than `limit` moves.
+    pub(super) fn depth_limited(&mut self, limit: u32, counter: &mut u32) -> Option<Solution> {
+        let initial = self.initial()?;
+        let mut path = vec![initial];
+
+        match self.bounded_search(&mut path, ActionType::None, 0, limit, None, counter) {
+            Bound::Found => Some(path_solution(path)),
+            Bound::Exhausted => {
+                self.partial = self.closest_on_path(&path);
+                None
+            },
+            Bound::Next(_) => None,
+        }
+    }
+
+    /// The start of `path`, up to the state on it closest to the goal.
+    fn closest_on_path(&self, path: &[Puzzle]) -> Option<Solution> {
+        let end = (0..path.len()).min_by_key(|i| (path[*i].heuristic_distances(&self.goal), *i))?;
+        Some(path_solution(path[..=end].to_vec()))
+    }
+
+    /// Depth-first search below the last state of `path`, reached by `previous`,
+    /// pruning nodes with f > bound. Without a heuristic f is just the depth.
+    fn bounded_search(&mut self, path: &mut Vec<Puzzle>, previous: ActionType, g: u32, bound: u32, heuristic: Option<Heuristic>, counter: &mut u32) -> Bound {
+        let state = path.last().unwrap().clone();
+        let h = match heuristic {
+            Some(heuristic) => self.estimate(&state, &self.goal, heuristic),
+            None => 0,
+        };
+        let f = g + h;
+        if f > bound {
+            return Bound::Next(f);
+        }
+        if state == self.goal {
+            return Bound::Found;
+        }
+        if *counter == 0 || self.limit_reached(path.len()) {
+            return Bound::Exhausted;
+        }
+        *counter -= 1;
+        // Only the current path is in memory.
+        self.stats.record_peaks(path.len(), 0);
+        self.observer.on_expand(&state, g);
+
+        let mut next = u32::MAX;
+        for (action, child) in state.successors(previous) {
+            self.stats.generated += 1;
+            self.observer.on_generate(&child, g + 1);
+            // Skip longer cycles back onto the current path.
+            if path.contains(&child) {
+                self.stats.duplicates += 1;
+                continue;
+            }
+            path.push(child);
+            match self.bounded_search(path, action, g + 1, bound, heuristic, counter) {
+                Bound::Found => return Bound::Found,
+                Bound::Next(f) => next = next.min(f),
+                Bound::Exhausted => return Bound::Exhausted,
+            }
+            path.pop();
+        }
+
+        Bound::Next(next)
+    }
+}
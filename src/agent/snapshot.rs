@@ -0,0 +1,307 @@
+use std::{collections::HashSet, fs::File, io::{BufReader, BufWriter, Read, Write}};
+use super::*;
+use crate::{error::SnapshotError, vector::Vector2};
+
+const MAGIC: &[u8; 4] = b"SAGT";
+const VERSION: u8 = 2;
+const ROOT: u32 = u32::MAX; // Parent index written for the root.
+const FRONTIER: u8 = 1;
+const EXPLORED: u8 = 2;
+
+/// One tree node as stored in a snapshot.
+struct Record {
+    parent: u32,
+    flags: u8,
+    priority: u32,
+    path: Path,
+}
+
+impl Agent {
+    /// Writes the search tree, frontier, explored set and counters to a file, so a
+    /// search that stopped early can be carried on after a restart with `Agent::load`.
+    /// The observer, limits and pattern database aren't saved and need setting again.
+    ///
+    /// Numbers are little-endian. Tiles take one byte each on boards of up to 256
+    /// cells and four otherwise, and tree nodes are stored parents first.
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), SnapshotError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let dimension = self.goal.dimension();
+        let cells = (dimension.x * dimension.y) as usize;
+
+        file.write_all(MAGIC)?;
+        file.write_all(&[VERSION])?;
+        file.write_all(&(dimension.x as u32).to_le_bytes())?;
+        file.write_all(&(dimension.y as u32).to_le_bytes())?;
+        write_tiles(&mut file, &self.goal.tiles())?;
+
+        match self.session {
+            Some(session) => {
+                let (strategy, parameter) = strategy_code(session.strategy);
+                file.write_all(&[1, strategy])?;
+                file.write_all(&parameter.to_le_bytes())?;
+                file.write_all(&[heuristic_code(session.heuristic)])?;
+            },
+            None => file.write_all(&[0])?,
+        }
+        file.write_all(&self.lower_bound.to_le_bytes())?;
+
+        let stats = self.stats;
+        for count in [stats.expanded, stats.generated, stats.duplicates, stats.reopened, stats.heuristic_evaluations] {
+            file.write_all(&count.to_le_bytes())?;
+        }
+        let nanos = u64::try_from(stats.elapsed.as_nanos()).unwrap_or(u64::MAX);
+        for size in [stats.peak_frontier as u64, stats.peak_explored as u64, nanos] {
+            file.write_all(&size.to_le_bytes())?;
+        }
+
+        let root = self.tree.root().ok_or(SnapshotError::Corrupt("the agent has no start state"))?;
+        let explored: HashSet<NodeId> = self.explored_hmap.values().copied().collect();
+        let nodes: Vec<_> = root.traverse_pre_order().collect();
+        let mut index = HashMap::with_capacity(nodes.len());
+        file.write_all(&(nodes.len() as u64).to_le_bytes())?;
+        for (i, node) in nodes.iter().enumerate() {
+            index.insert(node.node_id(), i as u32);
+            let parent = match node.parent() {
+                Some(parent) => index[&parent.node_id()],
+                None => ROOT,
+            };
+            let priority = self.frontier_prique.get_priority(&node.node_id()).map(|Reverse(priority)| *priority);
+            let mut flags = 0;
+            if priority.is_some() {
+                flags |= FRONTIER;
+            }
+            if explored.contains(&node.node_id()) {
+                flags |= EXPLORED;
+            }
+
+            let data = node.data();
+            file.write_all(&parent.to_le_bytes())?;
+            file.write_all(&[data.action.to_char() as u8, flags])?;
+            for value in [data.g, data.h, priority.unwrap_or(0)] {
+                file.write_all(&value.to_le_bytes())?;
+            }
+            write_tiles(&mut file, &data.state.tiles(cells))?;
+        }
+
+        file.write_all(&(self.queue.len() as u64).to_le_bytes())?;
+        for id in &self.queue {
+            let position = index.get(id).ok_or(SnapshotError::Corrupt("a queued node isn't in the tree"))?;
+            file.write_all(&position.to_le_bytes())?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Reads an agent written by `save`, ready to carry on its search.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Agent, SnapshotError> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SnapshotError::Corrupt("not an agent snapshot"));
+        }
+        if read_u8(&mut file)? != VERSION {
+            return Err(SnapshotError::Corrupt("unsupported version"));
+        }
+        let (rows, columns) = (read_u32(&mut file)?, read_u32(&mut file)?);
+        let cells = match (i32::try_from(rows), i32::try_from(columns)) {
+            (Ok(rows), Ok(columns)) if rows > 0 && columns > 0 => rows.checked_mul(columns),
+            _ => None,
+        };
+        let cells = cells.ok_or(SnapshotError::Corrupt("board size out of range"))? as usize;
+        let dimension = Vector2::new(rows as i32, columns as i32);
+        let goal = Puzzle::try_from_vec(dimension, read_tiles(&mut file, cells)?)
+            .map_err(|_| SnapshotError::Corrupt("goal is not a valid board"))?;
+
+        let session = match read_u8(&mut file)? {
+            0 => None,
+            1 => {
+                let code = read_u8(&mut file)?;
+                let strategy = strategy_from_code(code, read_u32(&mut file)?)?;
+                let heuristic = heuristic_from_code(read_u8(&mut file)?)?;
+                Some(Session { strategy, heuristic })
+            },
+            _ => return Err(SnapshotError::Corrupt("invalid session flag")),
+        };
+        let lower_bound = read_u32(&mut file)?;
+
+        let mut stats = SearchStats {
+            expanded: read_u32(&mut file)?,
+            generated: read_u32(&mut file)?,
+            duplicates: read_u32(&mut file)?,
+            reopened: read_u32(&mut file)?,
+            heuristic_evaluations: read_u32(&mut file)?,
+            ..SearchStats::default()
+        };
+        stats.peak_frontier = read_u64(&mut file)? as usize;
+        stats.peak_explored = read_u64(&mut file)? as usize;
+        stats.elapsed = Duration::from_nanos(read_u64(&mut file)?);
+
+        let count = read_u64(&mut file)? as usize;
+        if count == 0 {
+            return Err(SnapshotError::Corrupt("the tree is empty"));
+        }
+        let root = read_record(&mut file, dimension, cells)?;
+        if root.parent != ROOT {
+            return Err(SnapshotError::Corrupt("the tree doesn't start at its root"));
+        }
+        let initial = root.path.state.unpack_trusted(dimension);
+        let mut agent = Agent::new(initial, goal).map_err(|_| SnapshotError::Corrupt("the start can't reach the goal"))?;
+        agent.frontier_prique.clear();
+        agent.frontier_hmap.clear();
+
+        let Record { flags, priority, path, .. } = root;
+        let state = path.state.clone();
+        agent.tree = TreeBuilder::<Path>::new().with_root(path).build();
+        let root_id = agent.tree.root_id().ok_or(SnapshotError::Corrupt("the tree is empty"))?;
+        agent.restore_node(root_id, state, flags, priority);
+
+        let mut ids = Vec::with_capacity(count.min(1 << 20));
+        ids.push(root_id);
+        for _ in 1..count {
+            let Record { parent, flags, priority, path } = read_record(&mut file, dimension, cells)?;
+            if parent == ROOT {
+                return Err(SnapshotError::Corrupt("the tree has two roots"));
+            }
+            let parent_id = *ids.get(parent as usize).ok_or(SnapshotError::Corrupt("a node comes before its parent"))?;
+            let state = path.state.clone();
+            let id = agent.tree.get_mut(parent_id)
+                .ok_or(SnapshotError::Corrupt("a node's parent is missing"))?
+                .append(path).node_id();
+            ids.push(id);
+            agent.restore_node(id, state, flags, priority);
+        }
+
+        let queued = read_u64(&mut file)? as usize;
+        for _ in 0..queued {
+            let position = read_u32(&mut file)? as usize;
+            agent.queue.push_back(*ids.get(position).ok_or(SnapshotError::Corrupt("a queued node isn't in the tree"))?);
+        }
+
+        agent.session = session;
+        agent.lower_bound = lower_bound;
+        agent.evaluations.set(stats.heuristic_evaluations);
+        agent.stats = stats;
+        Ok(agent)
+    }
+
+    /// Puts a loaded node back in the frontier and explored set as its flags say.
+    fn restore_node(&mut self, id: NodeId, state: PackedBoard, flags: u8, priority: u32) {
+        if flags & FRONTIER != 0 {
+            self.frontier_prique.push(id, Reverse(priority));
+            self.frontier_hmap.insert(state.clone(), id);
+        }
+        if flags & EXPLORED != 0 {
+            self.explored_hmap.insert(state, id);
+        }
+    }
+}
+
+fn read_record(file: &mut impl Read, dimension: Vector2, cells: usize) -> Result<Record, SnapshotError> {
+    let parent = read_u32(file)?;
+    let action = ActionType::from_char(read_u8(file)? as char).ok_or(SnapshotError::Corrupt("invalid move"))?;
+    let flags = read_u8(file)?;
+    let (g, h, priority) = (read_u32(file)?, read_u32(file)?, read_u32(file)?);
+    let state = Puzzle::try_from_vec(dimension, read_tiles(file, cells)?)
+        .map_err(|_| SnapshotError::Corrupt("a state is not a valid board"))?;
+    Ok(Record { parent, flags, priority, path: Path::new(state, action, g, h) })
+}
+
+fn strategy_code(strategy: SearchStrategy) -> (u8, u32) {
+    match strategy {
+        SearchStrategy::BestFirst => (0, 0),
+        SearchStrategy::AStar => (1, 0),
+        SearchStrategy::IDAStar => (2, 0),
+        SearchStrategy::BreadthFirst => (3, 0),
+        SearchStrategy::DepthLimited(limit) => (4, limit),
+        SearchStrategy::UniformCost => (5, 0),
+        SearchStrategy::Bidirectional => (6, 0),
+        SearchStrategy::BidirectionalMM => (7, 0),
+        SearchStrategy::WeightedAStar(weight) => (8, weight.to_bits()),
+    }
+}
+
+fn strategy_from_code(code: u8, parameter: u32) -> Result<SearchStrategy, SnapshotError> {
+    match code {
+        0 => Ok(SearchStrategy::BestFirst),
+        1 => Ok(SearchStrategy::AStar),
+        2 => Ok(SearchStrategy::IDAStar),
+        3 => Ok(SearchStrategy::BreadthFirst),
+        4 => Ok(SearchStrategy::DepthLimited(parameter)),
+        5 => Ok(SearchStrategy::UniformCost),
+        6 => Ok(SearchStrategy::Bidirectional),
+        7 => Ok(SearchStrategy::BidirectionalMM),
+        8 => Ok(SearchStrategy::WeightedAStar(f32::from_bits(parameter))),
+        _ => Err(SnapshotError::Corrupt("unknown strategy")),
+    }
+}
+
+fn heuristic_code(heuristic: Heuristic) -> u8 {
+    match heuristic {
+        Heuristic::Misplaced => 0,
+        Heuristic::OrthoDistance => 1,
+        Heuristic::Inversions => 2,
+        Heuristic::LinearConflict => 3,
+        Heuristic::PatternDatabase => 4,
+    }
+}
+
+fn heuristic_from_code(code: u8) -> Result<Heuristic, SnapshotError> {
+    match code {
+        0 => Ok(Heuristic::Misplaced),
+        1 => Ok(Heuristic::OrthoDistance),
+        2 => Ok(Heuristic::Inversions),
+        3 => Ok(Heuristic::LinearConflict),
+        4 => Ok(Heuristic::PatternDatabase),
+        _ => Err(SnapshotError::Corrupt("unknown heuristic")),
+    }
+}
+
+/// Whether tiles on a board of this many cells need four bytes instead of one.
+fn wide(cells: usize) -> bool {
+    cells > u8::MAX as usize + 1
+}
+
+fn write_tiles(file: &mut impl Write, tiles: &[Tile]) -> Result<(), SnapshotError> {
+    match wide(tiles.len()) {
+        true => {
+            for tile in tiles {
+                file.write_all(&tile.to_le_bytes())?;
+            }
+        },
+        false => file.write_all(&tiles.iter().map(|tile| *tile as u8).collect::<Vec<u8>>())?,
+    }
+    Ok(())
+}
+
+fn read_tiles(file: &mut impl Read, cells: usize) -> Result<Vec<Tile>, SnapshotError> {
+    let width = if wide(cells) { 4 } else { 1 };
+    let mut bytes = Vec::new();
+    file.take((cells * width) as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != cells * width {
+        return Err(SnapshotError::Corrupt("unexpected end of file"));
+    }
+    Ok(match width {
+        4 => bytes.chunks_exact(4).map(|tile| Tile::from_le_bytes([tile[0], tile[1], tile[2], tile[3]])).collect(),
+        _ => bytes.into_iter().map(Tile::from).collect(),
+    })
+}
+
+fn read_u8(file: &mut impl Read) -> Result<u8, SnapshotError> {
+    let mut bytes = [0; 1];
+    file.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u32(file: &mut impl Read) -> Result<u32, SnapshotError> {
+    let mut bytes = [0; 4];
+    file.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(file: &mut impl Read) -> Result<u64, SnapshotError> {
+    let mut bytes = [0; 8];
+    file.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
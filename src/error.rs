@@ -0,0 +1,143 @@
+use std::{fmt, io};
+use crate::{agent::Heuristic, puzzle::Tile};
+
+/// Reasons a list of tiles can't be made into a `Puzzle`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PuzzleError {
+    /// The board has no rows or no columns.
+    ZeroDimension,
+    /// The board has more cells than can be counted.
+    TooLarge,
+    /// The number of tiles doesn't match rows * columns.
+    WrongLength { expected: usize, found: usize },
+    /// No tile is 0.
+    MissingBlank,
+    /// A tile is rows * columns or more.
+    OutOfRange(Tile),
+    /// A tile appears more than once.
+    DuplicateTile(Tile),
+    /// A rank is at least the number of layouts of the board.
+    RankOutOfRange(u128),
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PuzzleError::ZeroDimension => f.write_str("The board needs at least one row and one column."),
+            PuzzleError::TooLarge => f.write_str("The board has too many cells."),
+            PuzzleError::WrongLength { expected, found } => write!(f, "Expected {} tiles, found {}.", expected, found),
+            PuzzleError::MissingBlank => f.write_str("The board has no blank (0) tile."),
+            PuzzleError::OutOfRange(tile) => write!(f, "Tile {} is too large for the board.", tile),
+            PuzzleError::DuplicateTile(tile) => write!(f, "Tile {} appears more than once.", tile),
+            PuzzleError::RankOutOfRange(rank) => write!(f, "Rank {} is too large for the board.", rank),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+/// Reasons an `Agent` search can fail to produce a solution.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchError {
+    /// The start and goal boards have different sizes.
+    DimensionMismatch,
+    /// The goal can't be reached from the start, whatever the budget.
+    Unsolvable,
+    /// The node budget ran out before the goal was reached.
+    BudgetExhausted,
+    /// The search ran longer than its time limit.
+    TimeLimit,
+    /// The search needed to hold more states than its memory limit.
+    MemoryLimit,
+    /// The search was cancelled through its cancel flag.
+    Cancelled,
+    /// The heuristic can overestimate, so an optimal answer can't be proven.
+    InadmissibleHeuristic(Heuristic),
+    /// The pattern database heuristic was asked for without a database built for
+    /// every board the search estimates towards.
+    MissingPatternDatabase,
+    /// A weighted search was given a weight below 1 or one that isn't finite.
+    InvalidWeight(f32),
+    /// Everything the strategy could reach was searched without finding the goal,
+    /// e.g. a depth limit that is too shallow.
+    NoSolution,
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchError::DimensionMismatch => f.write_str("The start and goal boards have different sizes."),
+            SearchError::Unsolvable => f.write_str("The goal can't be reached from the start board."),
+            SearchError::BudgetExhausted => f.write_str("The node budget ran out before the goal was reached."),
+            SearchError::TimeLimit => f.write_str("The time limit ran out before the goal was reached."),
+            SearchError::MemoryLimit => f.write_str("The search needed more states than its memory limit allows."),
+            SearchError::Cancelled => f.write_str("The search was cancelled."),
+            SearchError::InadmissibleHeuristic(heuristic) =>
+                write!(f, "The {} heuristic is not admissible, so it can't prove optimality.", heuristic),
+            SearchError::MissingPatternDatabase => f.write_str("No pattern database matches the boards being searched towards."),
+            SearchError::InvalidWeight(weight) => write!(f, "The weight {} must be a finite number of at least 1.", weight),
+            SearchError::NoSolution => f.write_str("No solution found."),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+/// Reasons a pattern database can't be built, saved or loaded.
+#[derive(Debug)]
+pub enum PatternDatabaseError {
+    /// A pattern names the blank, a tile missing from the goal, or a tile used twice.
+    InvalidPartition(String),
+    /// The file isn't a pattern database or was cut short.
+    Corrupt(&'static str),
+    Io(io::Error),
+}
+
+impl fmt::Display for PatternDatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatternDatabaseError::InvalidPartition(reason) => write!(f, "Invalid tile partition: {}", reason),
+            PatternDatabaseError::Corrupt(reason) => write!(f, "Corrupt pattern database: {}", reason),
+            PatternDatabaseError::Io(error) => write!(f, "Pattern database I/O failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for PatternDatabaseError {}
+
+impl From<io::Error> for PatternDatabaseError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => PatternDatabaseError::Corrupt("unexpected end of file"),
+            _ => PatternDatabaseError::Io(error),
+        }
+    }
+}
+
+/// Reasons an `Agent` snapshot can't be saved or loaded.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The file isn't an agent snapshot or was cut short.
+    Corrupt(&'static str),
+    Io(io::Error),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Corrupt(reason) => write!(f, "Corrupt agent snapshot: {}", reason),
+            SnapshotError::Io(error) => write!(f, "Agent snapshot I/O failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => SnapshotError::Corrupt("unexpected end of file"),
+            _ => SnapshotError::Io(error),
+        }
+    }
+}
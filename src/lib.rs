@@ -0,0 +1,27 @@
+/*
+    Sliding Block Puzzle Solver
+    Thomas Abel
+    AI class
+    2022-10-18
+*/
+//! Sliding block puzzle model, heuristics and search agents.
+//!
+//! The binary in `main.rs` is a thin consumer of this library; other crates
+//! can depend on the same `Puzzle`, `Agent` and `Solution` types directly.
+pub mod puzzle;
+pub mod agent;
+pub mod vector;
+pub mod error;
+pub mod pdb;
+pub mod packed;
+pub mod observer;
+#[cfg(test)]
+mod test;
+
+pub use puzzle::{Puzzle, ActionType, Tile};
+pub use agent::{Agent, Solution, Certificate, SearchStats, SearchLimits, SearchStrategy, Heuristic};
+pub use error::{PuzzleError, SearchError, PatternDatabaseError, SnapshotError};
+pub use pdb::PatternDatabase;
+pub use packed::PackedBoard;
+pub use observer::{SearchObserver, SilentObserver, ConsoleObserver};
+pub use vector::Vector2;
@@ -4,134 +4,449 @@
     AI class
     2022-10-18
 */
-mod puzzle;
-mod agent;
-mod vector;
-mod test;
+use std::{process, sync::Arc, time::Duration};
+use ndarray_rand::rand::{thread_rng, Rng};
+use sliding_puzzle::*;
 
-use puzzle::*;
-use agent::*;
-use vector::Vector2;
+const USAGE: &str = "\
+Usage: sliding-puzzle <command> [options]
+
+Commands:
+    solve             Solve one board and print the solution.
+    generate          Print random boards that can reach the goal board.
+    benchmark         Run strategy and heuristic combinations on random boards.
+    check-solvable    Exit with 0 if the start board can reach the goal board, 1 if not.
+    help              Show this message.
+
+Options:
+    --size <RxC>          Board rows and columns (default 3x3).
+    --start <tiles>       Start board, row-major, 0 or _ for the blank (e.g. 4,1,3,0,2,6,7,5,8).
+    --goal <tiles>        Goal board (default: tiles in order, blank last).
+    --strategy <name>     best-first | astar | idastar | breadth-first | uniform-cost | depth-limited:<n> |
+                          bidirectional | bidirectional-mm | weighted-astar:<w>
+                          (benchmark runs the informed strategies if omitted).
+    --heuristic <name>    misplaced | manhattan | inversions | linear-conflict | pdb
+                          (benchmark runs all but pdb if omitted).
+    --budget <n>          Node budget for each search (default 1000000).
+    --time-limit <s>      Seconds allowed for each search (default: no limit).
+    --max-nodes <n>       States each search may hold in memory (default: no limit).
+    --count <n>           Boards to generate, or trials per benchmark combination (default 5).
+    --format <name>       text | json (default text).
+    --optimal             Solve with A* and certify the solution is minimal.
+    --pdb <file>          Pattern database for the pdb heuristic, built and saved there if missing.
+    --partition <groups>  Tiles in each pattern, e.g. 1,2,3,4;5,6,7,8 (default: groups of five).";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+}
+
+struct Options {
+    dimension: Vector2,
+    start: Option<Vec<Tile>>,
+    goal: Option<Vec<Tile>>,
+    strategy: Option<SearchStrategy>,
+    heuristic: Option<Heuristic>,
+    budget: u32,
+    time_limit: Option<Duration>,
+    max_nodes: Option<usize>,
+    count: usize,
+    format: Format,
+    optimal: bool,
+    pdb: Option<String>,
+    partition: Option<Vec<Vec<Tile>>>,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            dimension: Vector2::new(3, 3),
+            start: None,
+            goal: None,
+            strategy: None,
+            heuristic: None,
+            budget: 1_000_000,
+            time_limit: None,
+            max_nodes: None,
+            count: 5,
+            format: Format::Text,
+            optimal: false,
+            pdb: None,
+            partition: None,
+        }
+    }
+}
+
+enum Command {
+    Solve,
+    Generate,
+    Benchmark,
+    CheckSolvable,
+    Help,
+}
 
 fn main() {
-    experiment();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, options) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        },
+    };
+
+    let result = match command {
+        Command::Solve => solve(&options),
+        Command::Generate => generate(&options),
+        Command::Benchmark => benchmark(&options),
+        Command::CheckSolvable => check_solvable(&options),
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        },
+    };
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
+
+// Argument parsing
+fn parse_args(args: &[String]) -> Result<(Command, Options), String> {
+    let command = match args.first().map(String::as_str) {
+        Some("solve") => Command::Solve,
+        Some("generate") => Command::Generate,
+        Some("benchmark") => Command::Benchmark,
+        Some("check-solvable") => Command::CheckSolvable,
+        Some("help") | Some("--help") | Some("-h") | None => Command::Help,
+        Some(other) => return Err(format!("Unknown command '{}'.", other)),
+    };
+
+    let mut options = Options::default();
+    let mut rest = args.iter().skip(1);
+    while let Some(flag) = rest.next() {
+        if flag == "--optimal" {
+            options.optimal = true;
+            continue;
+        }
+        let value = rest.next().ok_or_else(|| format!("Missing value for '{}'.", flag))?;
+        match flag.as_str() {
+            "--size" => options.dimension = parse_size(value)?,
+            "--start" => options.start = Some(parse_tiles(value)?),
+            "--goal" => options.goal = Some(parse_tiles(value)?),
+            "--strategy" => options.strategy = Some(value.parse()?),
+            "--heuristic" => options.heuristic = Some(value.parse()?),
+            "--budget" => options.budget = parse_number(flag, value)?,
+            "--time-limit" => options.time_limit = Some(parse_seconds(value)?),
+            "--max-nodes" => options.max_nodes = Some(parse_number(flag, value)?),
+            "--count" => options.count = match parse_number(flag, value)? {
+                0 => return Err("--count needs to be at least 1.".to_string()),
+                count => count,
+            },
+            "--pdb" => options.pdb = Some(value.clone()),
+            "--partition" => options.partition = Some(value.split(';').map(parse_tiles).collect::<Result<_, _>>()?),
+            "--format" => options.format = match value.as_str() {
+                "text" => Format::Text,
+                "json" => Format::Json,
+                _ => return Err(format!("Unknown format '{}'.", value)),
+            },
+            _ => return Err(format!("Unknown option '{}'.", flag)),
+        }
+    }
+    Ok((command, options))
+}
+
+fn parse_size(value: &str) -> Result<Vector2, String> {
+    let (rows, columns) = value.split_once(['x', 'X'])
+        .ok_or_else(|| format!("Board size '{}' should look like 3x3.", value))?;
+    let rows = parse_number("--size", rows)?;
+    let columns = parse_number("--size", columns)?;
+    if rows <= 0 || columns <= 0 {
+        return Err(PuzzleError::ZeroDimension.to_string());
+    }
+    Ok(Vector2::new(rows, columns))
+}
+
+fn parse_tiles(value: &str) -> Result<Vec<Tile>, String> {
+    value.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|tile| !tile.is_empty())
+        .map(|tile| match tile {
+            "_" => Ok(0),
+            _ => parse_number("tile", tile),
+        })
+        .collect()
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    let seconds: f64 = parse_number("--time-limit", value)?;
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("Invalid value '{}' for --time-limit.", value))
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.trim().parse().map_err(|_| format!("Invalid value '{}' for {}.", value, name))
+}
+
+fn board(dimension: Vector2, tiles: Vec<Tile>) -> Result<Puzzle, String> {
+    Puzzle::try_from_vec(dimension, tiles).map_err(|e| e.to_string())
+}
+
+fn goal_board(options: &Options) -> Result<Puzzle, String> {
+    match &options.goal {
+        Some(tiles) => board(options.dimension, tiles.clone()),
+        None => Puzzle::try_goal(options.dimension).map_err(|e| e.to_string()),
+    }
+}
+
+fn start_board(options: &Options) -> Result<Puzzle, String> {
+    let tiles = options.start.clone().ok_or("A start board is required (--start).")?;
+    board(options.dimension, tiles)
+}
+
+/// Loads or builds the pattern database when the pdb heuristic is in play.
+fn pattern_database(options: &Options, goal: &Puzzle, heuristics: &[Heuristic]) -> Result<Option<Arc<PatternDatabase>>, String> {
+    if !heuristics.contains(&Heuristic::PatternDatabase) {
+        return Ok(None);
+    }
+    let partition = options.partition.clone().unwrap_or_else(|| PatternDatabase::default_partition(goal));
+    let database = match &options.pdb {
+        Some(path) => PatternDatabase::load_or_build(path, goal, &partition),
+        None => PatternDatabase::build(goal, &partition),
+    };
+    database.map(|database| Some(Arc::new(database))).map_err(|e| e.to_string())
+}
+
+fn random_solvable(goal: &Puzzle) -> Puzzle {
+    // On a single row or column the tiles can't pass each other, so only the blank's
+    // spot can vary and reshuffling would almost never land on a solvable board.
+    let dimension = goal.dimension();
+    if dimension.x == 1 || dimension.y == 1 {
+        let mut tiles: Vec<Tile> = goal.tiles().into_iter().filter(|tile| *tile != 0).collect();
+        tiles.insert(thread_rng().gen_range(0..=tiles.len()), 0);
+        return Puzzle::from_vec(dimension, tiles);
+    }
+    let mut puzzle = Puzzle::new(goal.dimension());
+    while !puzzle.is_solvable_to(goal) {
+        puzzle = Puzzle::new(goal.dimension());
+    }
+    puzzle
+}
+
+// Commands
+fn solve(options: &Options) -> Result<(), String> {
+    let start = start_board(options)?;
+    let goal = goal_board(options)?;
+    // Optimal solving always runs A*, whatever strategy was asked for.
+    let strategy = match options.optimal {
+        true => SearchStrategy::AStar,
+        false => options.strategy.unwrap_or(SearchStrategy::AStar),
+    };
+    let heuristic = options.heuristic.unwrap_or(Heuristic::OrthoDistance);
+
+    let database = pattern_database(options, &goal, &[heuristic])?;
+
+    let mut agent = Agent::new(start, goal).map_err(|e| e.to_string())?;
+    if let Some(database) = database {
+        agent.set_pattern_database(database);
+    }
+    agent.set_limits(limits(options));
+    // JSON output stays machine readable, so only text shows progress.
+    if options.format == Format::Text {
+        agent.set_observer(Box::new(ConsoleObserver));
+    }
+    let result = match options.optimal {
+        true => agent.solve_optimal(heuristic, options.budget),
+        false => agent.uniform_cost_search(strategy, heuristic, options.budget),
+    };
+    let solution = match result {
+        Ok(solution) => solution,
+        Err(error) => {
+            if let (Format::Text, Some(partial)) = (options.format, agent.best_partial()) {
+                if let Some(closest) = partial.state_path.last() {
+                    closest.print(&format!("Closest state reached, after {} moves ({}):", partial.steps, partial.moves()));
+                }
+            }
+            return Err(error.to_string());
+        },
+    };
+
+    match options.format {
+        Format::Text => {
+            solution.print();
+            if let Some(certificate) = solution.certificate {
+                println!("Optimal: proven by {} (lower bound {})", certificate.heuristic, certificate.lower_bound);
+            }
+        },
+        Format::Json => println!(
+            "{{\"strategy\":\"{}\",\"heuristic\":\"{}\",\"steps\":{},\"moves\":\"{}\",\"optimal\":{},\"stats\":{},\"states\":[{}]}}",
+            strategy, heuristic, solution.steps, solution.moves(), solution.is_optimal(), json_stats(&solution),
+            solution.state_path.iter().map(json_tiles).collect::<Vec<_>>().join(",")
+        ),
+    }
+    Ok(())
+}
+
+fn generate(options: &Options) -> Result<(), String> {
+    let goal = goal_board(options)?;
+    let puzzles: Vec<Puzzle> = (0..options.count).map(|_| random_solvable(&goal)).collect();
+
+    match options.format {
+        Format::Text => {
+            for puzzle in &puzzles {
+                let tiles: Vec<String> = puzzle.tiles().iter().map(Tile::to_string).collect();
+                println!("{}", tiles.join(","));
+            }
+        },
+        Format::Json => println!("[{}]", puzzles.iter().map(json_tiles).collect::<Vec<_>>().join(",")),
+    }
+    Ok(())
 }
 
-fn experiment() {
-    println!("\n<---------- Starting the session. ---------->\n");
+fn check_solvable(options: &Options) -> Result<(), String> {
+    let start = start_board(options)?;
+    let goal = goal_board(options)?;
+    let solvable = start.is_solvable_to(&goal);
 
-    let dimension = Vector2::new(3, 3);
-    let puzzle = puzzles(dimension);
+    match options.format {
+        Format::Text => println!("{}", if solvable { "solvable" } else { "unsolvable" }),
+        Format::Json => println!("{{\"solvable\":{}}}", solvable),
+    }
+    if !solvable {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn benchmark(options: &Options) -> Result<(), String> {
+    let goal = goal_board(options)?;
+    let initial: Vec<Puzzle> = (0..options.count).map(|_| random_solvable(&goal)).collect();
+    let strategies = match options.strategy {
+        Some(strategy) => vec![strategy],
+        None => vec![SearchStrategy::BestFirst, SearchStrategy::AStar, SearchStrategy::IDAStar],
+    };
+    let heuristics = match options.heuristic {
+        Some(heuristic) => vec![heuristic],
+        None => vec![Heuristic::Misplaced, Heuristic::OrthoDistance, Heuristic::Inversions, Heuristic::LinearConflict],
+    };
+
+    let database = pattern_database(options, &goal, &heuristics)?;
+
+    if options.format == Format::Text {
+        println!("\n<---------- Starting the session. ---------->\n");
+    }
     let mut solutions = Vec::new();
-    let loop_count = 1_000_000;
 
     // Run a trial for each strategy and heuristic combination.
-    for search_strategy in [SearchStrategy::BestFirst, SearchStrategy::AStar] {
-        for heuristic in [Heuristic::Misplaced, Heuristic::OrthoDistance, Heuristic::Inversions] {
-            // Run the trial 5 times using the same set of 5 initial states.
-            for i in 0..5 {
-                let mut agent = Agent::new(puzzle[3 + i].clone(), puzzle[0].clone());
-                let label = format!("{} + {}", search_strategy.to_string(), heuristic.to_string());
-                match agent.uniform_cost_search(search_strategy, heuristic, loop_count) {
-                    None => {
-                        solutions.push((label, Err("\nNo Solution found.")));
-                    },
-                    Some(sol) => {
-                        solutions.push((label, Ok(sol)));
-                    },
+    for search_strategy in &strategies {
+        for heuristic in &heuristics {
+            // Run the trials using the same set of initial states.
+            for puzzle in &initial {
+                let mut agent = Agent::new(puzzle.clone(), goal.clone()).map_err(|e| e.to_string())?;
+                if let Some(database) = &database {
+                    agent.set_pattern_database(database.clone());
                 }
-
+                // JSON output stays machine readable, so only text shows progress.
+                if options.format == Format::Text {
+                    agent.set_observer(Box::new(ConsoleObserver));
+                }
+                agent.set_limits(limits(options));
+                let label = format!("{} + {}", search_strategy, heuristic);
+                solutions.push((label, agent.uniform_cost_search(*search_strategy, *heuristic, options.budget)));
             }
         }
     }
 
-    analyze_solutions(solutions, 6, 5);
-
-    println!("\n<----------  Ending the session.  ---------->\n");
+    let categories = strategies.len() * heuristics.len();
+    match options.format {
+        Format::Text => {
+            analyze_solutions(solutions, categories, initial.len());
+            println!("\n<----------  Ending the session.  ---------->\n");
+        },
+        Format::Json => println!("{}", json_benchmark(&solutions, categories, initial.len())),
+    }
+    Ok(())
 }
 
-fn analyze_solutions(solutions: Vec<(String, Result<Solution, &str>)>, categories: usize, trials: usize) {
-    for i in 0..categories as usize {
+fn analyze_solutions(solutions: Vec<(String, Result<Solution, SearchError>)>, categories: usize, trials: usize) {
+    for i in 0..categories {
         // Track average steps for each category.
         let mut steps = 0;
+        let mut expanded = 0;
         let mut count = 0;
-        let mut least_steps = u32::max_value();
-        let mut index = 0;
+        let mut least_steps = u32::MAX;
+        let mut index = None;
 
-        let mut k = i * trials;
         println!("{}", &solutions[i * trials].0);
-        for j in 0.. trials as usize {
-            k = i * trials + j;
+        for j in 0..trials {
+            let k = i * trials + j;
             let sol = &solutions[k];
-        
+
             match &sol.1 {
                 Ok(sol) => {
                     count += 1;
                     steps += sol.steps;
+                    expanded += sol.stats.expanded as u64;
                     if sol.steps < least_steps {
                         least_steps = sol.steps;
-                        index = k;
+                        index = Some(k);
                     }
                 },
-                Err(message) => {
-                    println!("{}", message);
+                Err(error) => {
+                    println!("\n{}", error);
                 },
             }
         }
-        println!("Best Solution: ");
-        solutions[index].1.as_ref().unwrap().print();
-        let avg_steps = steps as f32 / count as f32;
-        println!("Average Steps: {}", avg_steps);
-    }
-
-}
-
-fn puzzles(dimension: Vector2) -> Vec<Puzzle> {
-    let puzzle_raw:Vec<Vec<u8>>;
-    puzzle_raw = vec![
-            // goal
-            vec![
-                1, 2, 3, 
-                4, 5, 6, 
-                7, 8, 0
-            ],
-            // from homework sheet
-            vec![
-                4, 5, 0, 
-                6, 1, 8, 
-                7, 3, 2
-            ],
-            // trivial
-            vec![
-                4, 1, 3, 
-                0, 2, 6, 
-                7, 5, 8
-            ],
-    ];
-    let mut puzzle = Vec::new();
-    for p in puzzle_raw {
-        puzzle.push(Puzzle::from_vec(dimension, p));
-    }
-
-    // Add random puzzles
-    for i in 0..5 {
-        let mut random_puzzle = Puzzle::new(dimension);
-        while !random_puzzle.test_solvable() {
-            random_puzzle = Puzzle::new(dimension);
+        if let Some(index) = index {
+            println!("Best Solution: ");
+            solutions[index].1.as_ref().unwrap().print();
+            let avg_steps = steps as f32 / count as f32;
+            println!("Average Steps: {}", avg_steps);
+            println!("Average Expanded: {}", expanded as f32 / count as f32);
         }
-        puzzle.push(random_puzzle);
     }
-    
-    puzzle
+
 }
 
-#[test]
-fn solvable() {
-    let dimension = Vector2::new(3, 3);
-    let puzzle = puzzles(dimension);
+fn limits(options: &Options) -> SearchLimits {
+    SearchLimits { time: options.time_limit, max_nodes: options.max_nodes, cancel: None }
+}
+
+fn json_stats(solution: &Solution) -> String {
+    let stats = solution.stats;
+    format!(
+        "{{\"expanded\":{},\"generated\":{},\"duplicates\":{},\"reopened\":{},\"peak_frontier\":{},\"peak_explored\":{},\"heuristic_evaluations\":{},\"branching_factor\":{:.4},\"seconds\":{:.6}}}",
+        stats.expanded, stats.generated, stats.duplicates, stats.reopened, stats.peak_frontier, stats.peak_explored,
+        stats.heuristic_evaluations, stats.effective_branching_factor(solution.steps), stats.elapsed.as_secs_f64()
+    )
+}
+
+/// One object per strategy and heuristic combination, with the same figures `analyze_solutions` prints.
+fn json_benchmark(solutions: &[(String, Result<Solution, SearchError>)], categories: usize, trials: usize) -> String {
+    let objects: Vec<String> = (0..categories).map(|i| {
+        let category = &solutions[i * trials..(i + 1) * trials];
+        let solved: Vec<&Solution> = category.iter().filter_map(|(_, result)| result.as_ref().ok()).collect();
+        let errors: Vec<String> = category.iter()
+            .filter_map(|(_, result)| result.as_ref().err())
+            .map(|error| format!("\"{}\"", error))
+            .collect();
+        let average = |total: u64| match solved.len() {
+            0 => "null".to_string(),
+            count => format!("{:.4}", total as f64 / count as f64),
+        };
+        let steps = solved.iter().map(|solution| solution.steps as u64).sum();
+        let expanded = solved.iter().map(|solution| solution.stats.expanded as u64).sum();
+        let best = match solved.iter().min_by_key(|solution| solution.steps) {
+            Some(best) => format!("{{\"steps\":{},\"moves\":\"{}\"}}", best.steps, best.moves()),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"combination\":\"{}\",\"solved\":{},\"average_steps\":{},\"average_expanded\":{},\"best\":{},\"errors\":[{}]}}",
+            category[0].0, solved.len(), average(steps), average(expanded), best, errors.join(",")
+        )
+    }).collect();
+    format!("[{}]", objects.join(","))
+}
 
-    assert!(puzzle[0].test_solvable());
-    assert!(!puzzle[1].test_solvable());
-    assert!(puzzle[2].test_solvable());
-    assert!(puzzle[3].test_solvable());
+fn json_tiles(puzzle: &Puzzle) -> String {
+    let tiles: Vec<String> = puzzle.tiles().iter().map(Tile::to_string).collect();
+    format!("[{}]", tiles.join(","))
 }
@@ -0,0 +1,34 @@
+use crate::{puzzle::Puzzle, agent::{Solution, SearchStats}};
+
+/// Hooks an `Agent` calls while it searches, for logging, progress bars or
+/// collecting traces. Every method does nothing unless overridden.
+pub trait SearchObserver {
+    /// Called once before the search starts, with the starting board.
+    fn on_start(&mut self, _initial: &Puzzle) {}
+    /// Called when a state's successors are about to be generated.
+    fn on_expand(&mut self, _state: &Puzzle, _g: u32) {}
+    /// Called for every successor created, including ones later dropped as duplicates.
+    fn on_generate(&mut self, _state: &Puzzle, _g: u32) {}
+    /// Called with each solution as it is found.
+    fn on_goal(&mut self, _solution: &Solution) {}
+    /// Called when the node budget runs out before the search is done.
+    fn on_budget_exhausted(&mut self, _stats: &SearchStats) {}
+    /// Called once when the search ends, however it ended.
+    fn on_finish(&mut self, _stats: &SearchStats) {}
+}
+
+/// Ignores everything. Agents use this unless given another observer.
+pub struct SilentObserver;
+impl SearchObserver for SilentObserver {}
+
+/// Prints the starting board, then the nodes expanded and seconds taken once the search ends.
+pub struct ConsoleObserver;
+impl SearchObserver for ConsoleObserver {
+    fn on_start(&mut self, initial: &Puzzle) {
+        initial.print("Initial");
+    }
+
+    fn on_finish(&mut self, stats: &SearchStats) {
+        println!("{:6} {:.6} s", stats.expanded, stats.elapsed.as_secs_f32());
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{puzzle::{Puzzle, Tile}, error::PuzzleError, vector::Vector2};
+
+/// A board packed into as little memory as possible, for hashing and storing
+/// the many states a search visits. It doesn't record the board's size, so
+/// unpacking needs the dimension back.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PackedBoard {
+    /// Boards of up to 16 cells, 4 bits per tile with the first cell lowest.
+    Nibbles(u64),
+    /// Boards of up to 256 cells, one byte per tile.
+    Bytes(Box<[u8]>),
+    /// Anything larger, one full `Tile` each.
+    Wide(Box<[Tile]>),
+}
+
+impl PackedBoard {
+    /// Packs row-major tiles into the smallest encoding they fit.
+    pub fn from_tiles(tiles: &[Tile]) -> PackedBoard {
+        let largest = tiles.iter().copied().max().unwrap_or(0);
+        if tiles.len() <= 16 && largest < 16 {
+            let bits = tiles.iter().enumerate()
+                .fold(0u64, |bits, (cell, tile)| bits | ((*tile as u64) << (4 * cell)));
+            PackedBoard::Nibbles(bits)
+        } else if largest <= u8::MAX as Tile {
+            PackedBoard::Bytes(tiles.iter().map(|tile| *tile as u8).collect())
+        } else {
+            PackedBoard::Wide(tiles.into())
+        }
+    }
+
+    /// The row-major tiles for a board with `cells` cells. Nibbles hold at most 16.
+    pub fn tiles(&self, cells: usize) -> Vec<Tile> {
+        match self {
+            PackedBoard::Nibbles(bits) => (0..cells.min(16)).map(|cell| ((bits >> (4 * cell)) & 0xF) as Tile).collect(),
+            PackedBoard::Bytes(bytes) => bytes.iter().map(|tile| *tile as Tile).collect(),
+            PackedBoard::Wide(tiles) => tiles.to_vec(),
+        }
+    }
+
+    /// Rebuilds the puzzle this board was packed from, or an error if the tiles
+    /// don't make a valid board of the given dimension.
+    pub fn unpack(&self, dimension: Vector2) -> Result<Puzzle, PuzzleError> {
+        let cells = (dimension.x.max(0) as usize).saturating_mul(dimension.y.max(0) as usize);
+        Puzzle::try_from_vec(dimension, self.tiles(cells))
+    }
+
+    /// Rebuilds a board the crate packed itself, skipping validation.
+    pub(crate) fn unpack_trusted(&self, dimension: Vector2) -> Puzzle {
+        let cells = (dimension.x * dimension.y) as usize;
+        Puzzle::from_packed(dimension, self.tiles(cells))
+    }
+}
+
+impl From<&Puzzle> for PackedBoard {
+    fn from(puzzle: &Puzzle) -> Self {
+        puzzle.pack()
+    }
+}
@@ -0,0 +1,288 @@
+use std::{collections::VecDeque, fs::File, io::{BufReader, BufWriter, Read, Write}, path::Path};
+use crate::{puzzle::{Puzzle, Tile}, vector::Vector2, error::PatternDatabaseError};
+
+const MAGIC: &[u8; 4] = b"SPDB";
+const VERSION: u8 = 2;
+const UNSEEN: u8 = u8::MAX;
+
+/// Disjoint additive pattern databases for one goal layout.
+///
+/// Each pattern is a group of tiles. Its table holds, for every placement of
+/// those tiles, the fewest moves of pattern tiles needed to bring them home,
+/// found by a backward breadth-first search from the goal. Only pattern tile
+/// moves are counted, so the tables of disjoint patterns can be summed and the
+/// total is still admissible.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PatternDatabase {
+    goal: Puzzle,
+    patterns: Vec<Pattern>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Pattern {
+    tiles: Vec<Tile>,
+    table: Vec<u8>, // Indexed by the rank of the tiles' positions.
+}
+
+impl PatternDatabase {
+    /// Generates a table for every group of tiles in `partition`.
+    pub fn build(goal: &Puzzle, partition: &[Vec<Tile>]) -> Result<PatternDatabase, PatternDatabaseError> {
+        validate(goal, partition)?;
+        let patterns = partition.iter().map(|tiles| Pattern::build(goal, tiles)).collect();
+        Ok(PatternDatabase { goal: goal.clone(), patterns })
+    }
+
+    /// Splits the goal's tiles, in reading order, into groups of at most five.
+    pub fn default_partition(goal: &Puzzle) -> Vec<Vec<Tile>> {
+        let tiles: Vec<Tile> = goal.tiles().into_iter().filter(|tile| *tile != 0).collect();
+        tiles.chunks(5).map(|chunk| chunk.to_vec()).collect()
+    }
+
+    pub fn goal(&self) -> &Puzzle {
+        &self.goal
+    }
+
+    /// The groups of tiles this database was built from.
+    pub fn partition(&self) -> Vec<Vec<Tile>> {
+        self.patterns.iter().map(|pattern| pattern.tiles.clone()).collect()
+    }
+
+    /// Sum of every pattern's distance for the given state.
+    pub fn heuristic(&self, state: &Puzzle) -> u32 {
+        let dimension = self.goal.dimension();
+        let cells = (dimension.x * dimension.y) as usize;
+        let mut location = vec![0; cells];
+        for (cell, tile) in state.tiles().into_iter().enumerate() {
+            if let Some(spot) = location.get_mut(tile as usize) {
+                *spot = cell;
+            }
+        }
+        self.patterns.iter().map(|pattern| pattern.lookup(&location, cells)).sum()
+    }
+
+    // Files
+    /// Writes the database in a compact binary format. Tiles are stored as
+    /// little-endian `u32`s and table entries as single bytes.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PatternDatabaseError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let dimension = self.goal.dimension();
+
+        file.write_all(MAGIC)?;
+        file.write_all(&[VERSION])?;
+        file.write_all(&(dimension.x as u32).to_le_bytes())?;
+        file.write_all(&(dimension.y as u32).to_le_bytes())?;
+        write_tiles(&mut file, &self.goal.tiles())?;
+        file.write_all(&(self.patterns.len() as u32).to_le_bytes())?;
+        for pattern in &self.patterns {
+            file.write_all(&(pattern.tiles.len() as u32).to_le_bytes())?;
+            write_tiles(&mut file, &pattern.tiles)?;
+            file.write_all(&(pattern.table.len() as u64).to_le_bytes())?;
+            file.write_all(&pattern.table)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Reads a database written by `save`.
+    pub fn load(path: impl AsRef<Path>) -> Result<PatternDatabase, PatternDatabaseError> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(PatternDatabaseError::Corrupt("not a pattern database"));
+        }
+        if read_bytes(&mut file, 1)?[0] != VERSION {
+            return Err(PatternDatabaseError::Corrupt("unsupported version"));
+        }
+        let (rows, columns) = (read_u32(&mut file)?, read_u32(&mut file)?);
+        let cells = match (i32::try_from(rows), i32::try_from(columns)) {
+            (Ok(rows), Ok(columns)) if rows > 0 && columns > 0 => rows.checked_mul(columns),
+            _ => None,
+        };
+        let Some(cells) = cells else {
+            return Err(PatternDatabaseError::Corrupt("board size out of range"));
+        };
+        let cells = cells as usize;
+        let dimension = Vector2::new(rows as i32, columns as i32);
+        let goal = Puzzle::try_from_vec(dimension, read_tiles(&mut file, cells)?)
+            .map_err(|_| PatternDatabaseError::Corrupt("goal is not a valid board"))?;
+
+        let count = read_u32(&mut file)? as usize;
+        let mut patterns = Vec::with_capacity(count.min(cells));
+        for _ in 0..count {
+            let size = read_u32(&mut file)? as usize;
+            let tiles = read_tiles(&mut file, size.min(cells))?;
+            let length = read_u64(&mut file)? as usize;
+            if length != placements(cells, tiles.len()) {
+                return Err(PatternDatabaseError::Corrupt("table size doesn't match its pattern"));
+            }
+            let table = read_bytes(&mut file, length)?;
+            patterns.push(Pattern { tiles, table });
+        }
+
+        let partition: Vec<Vec<Tile>> = patterns.iter().map(|pattern| pattern.tiles.clone()).collect();
+        validate(&goal, &partition).map_err(|_| PatternDatabaseError::Corrupt("invalid tile partition"))?;
+        Ok(PatternDatabase { goal, patterns })
+    }
+
+    /// Loads the database at `path` if it was built for the same goal and partition,
+    /// otherwise builds it and saves it there for next time.
+    pub fn load_or_build(path: impl AsRef<Path>, goal: &Puzzle, partition: &[Vec<Tile>]) -> Result<PatternDatabase, PatternDatabaseError> {
+        if let Ok(database) = PatternDatabase::load(&path) {
+            if database.goal == *goal && database.partition() == partition {
+                return Ok(database);
+            }
+        }
+        let database = PatternDatabase::build(goal, partition)?;
+        database.save(&path)?;
+        Ok(database)
+    }
+}
+
+impl Pattern {
+    /// Backward breadth-first search over placements of the pattern tiles and the blank.
+    /// Sliding a pattern tile costs 1 and any other tile costs 0, so a double-ended
+    /// queue keeps the search in order of cost.
+    fn build(goal: &Puzzle, tiles: &[Tile]) -> Pattern {
+        let dimension = goal.dimension();
+        let cells = (dimension.x * dimension.y) as usize;
+        let goal_tiles = goal.tiles();
+        let home = |tile: Tile| goal_tiles.iter().position(|t| *t == tile).unwrap();
+
+        let positions: Vec<usize> = tiles.iter().map(|tile| home(*tile)).collect();
+        let ranks = placements(cells, tiles.len());
+        let mut distance = vec![UNSEEN; ranks * cells];
+        let start = rank(&positions, cells) * cells + home(0);
+        distance[start] = 0;
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(index) = queue.pop_front() {
+            let moves = distance[index];
+            let (placement, blank) = (index / cells, index % cells);
+            let positions = unrank(placement, cells, tiles.len());
+
+            for next_blank in neighbours(blank, dimension) {
+                let (next_placement, cost) = match positions.iter().position(|p| *p == next_blank) {
+                    Some(i) => {
+                        let mut next = positions.clone();
+                        next[i] = blank;
+                        (rank(&next, cells), 1)
+                    },
+                    None => (placement, 0),
+                };
+                let next_index = next_placement * cells + next_blank;
+                if moves + cost < distance[next_index] {
+                    distance[next_index] = moves + cost;
+                    match cost {
+                        0 => queue.push_front(next_index),
+                        _ => queue.push_back(next_index),
+                    }
+                }
+            }
+        }
+
+        // The blank can be anywhere, so keep the cheapest entry for each placement.
+        let table = distance.chunks(cells)
+            .map(|entries| *entries.iter().min().unwrap())
+            .collect();
+        Pattern { tiles: tiles.to_vec(), table }
+    }
+
+    fn lookup(&self, location: &[usize], cells: usize) -> u32 {
+        let positions: Vec<usize> = self.tiles.iter().map(|tile| location[*tile as usize]).collect();
+        match self.table[rank(&positions, cells)] {
+            UNSEEN => 0,
+            moves => moves as u32,
+        }
+    }
+}
+
+/// Checks every tile is on the goal board, isn't the blank, and appears in one pattern only.
+fn validate(goal: &Puzzle, partition: &[Vec<Tile>]) -> Result<(), PatternDatabaseError> {
+    let goal_tiles = goal.tiles();
+    let mut used = vec![false; goal_tiles.len()];
+    for tile in partition.iter().flatten() {
+        if *tile == 0 {
+            return Err(PatternDatabaseError::InvalidPartition(String::from("the blank can't be part of a pattern")));
+        }
+        if !goal_tiles.contains(tile) {
+            return Err(PatternDatabaseError::InvalidPartition(format!("tile {} isn't on the board", tile)));
+        }
+        if std::mem::replace(&mut used[*tile as usize], true) {
+            return Err(PatternDatabaseError::InvalidPartition(format!("tile {} is in more than one pattern", tile)));
+        }
+    }
+    Ok(())
+}
+
+/// Number of ways to place `k` distinct tiles on `n` cells, n! / (n - k)!.
+fn placements(n: usize, k: usize) -> usize {
+    (n - k + 1..=n).product()
+}
+
+/// Ranks an ordered placement of distinct cells among all placements of that size.
+fn rank(positions: &[usize], cells: usize) -> usize {
+    let mut index = 0;
+    for (i, position) in positions.iter().enumerate() {
+        let smaller_used = positions[..i].iter().filter(|p| *p < position).count();
+        index = index * (cells - i) + *position - smaller_used;
+    }
+    index
+}
+
+/// Inverse of `rank`.
+fn unrank(mut index: usize, cells: usize, k: usize) -> Vec<usize> {
+    let mut digits = vec![0; k];
+    for i in (0..k).rev() {
+        let base = cells - i;
+        digits[i] = index % base;
+        index /= base;
+    }
+    let mut free: Vec<usize> = (0..cells).collect();
+    digits.into_iter().map(|digit| free.remove(digit)).collect()
+}
+
+/// Cells next to the given cell, in row-major numbering.
+fn neighbours(cell: usize, dimension: Vector2) -> impl Iterator<Item = usize> {
+    let (rows, columns) = (dimension.x as usize, dimension.y as usize);
+    let (row, column) = (cell / columns, cell % columns);
+    let up = (row > 0).then(|| cell - columns);
+    let down = (row + 1 < rows).then(|| cell + columns);
+    let left = (column > 0).then(|| cell - 1);
+    let right = (column + 1 < columns).then(|| cell + 1);
+    [up, down, left, right].into_iter().flatten()
+}
+
+fn read_bytes(file: &mut impl Read, length: usize) -> Result<Vec<u8>, PatternDatabaseError> {
+    let mut bytes = Vec::new();
+    file.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != length {
+        return Err(PatternDatabaseError::Corrupt("unexpected end of file"));
+    }
+    Ok(bytes)
+}
+
+fn read_tiles(file: &mut impl Read, count: usize) -> Result<Vec<Tile>, PatternDatabaseError> {
+    let bytes = read_bytes(file, count * 4)?;
+    Ok(bytes.chunks_exact(4).map(|tile| Tile::from_le_bytes([tile[0], tile[1], tile[2], tile[3]])).collect())
+}
+
+fn write_tiles(file: &mut impl Write, tiles: &[Tile]) -> Result<(), PatternDatabaseError> {
+    for tile in tiles {
+        file.write_all(&tile.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_u32(file: &mut impl Read) -> Result<u32, PatternDatabaseError> {
+    let mut bytes = [0; 4];
+    file.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(file: &mut impl Read) -> Result<u64, PatternDatabaseError> {
+    let mut bytes = [0; 8];
+    file.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
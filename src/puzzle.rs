@@ -1,37 +1,186 @@
+use std::fmt;
 use ndarray::prelude::*;
 use ndarray_rand::{rand::{seq::SliceRandom, thread_rng}};
-use crate::vector::Vector2;
+use crate::{vector::Vector2, error::PuzzleError, packed::PackedBoard};
 
-#[derive(Clone, Copy, PartialEq)]
+/// A move, named for the direction the blank travels.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum ActionType {
     None, Up, Down, Left, Right
 }
+impl ActionType {
+    /// Every real move, in the order searches try them.
+    pub const ALL: [ActionType; 4] = [ActionType::Up, ActionType::Down, ActionType::Left, ActionType::Right];
 
+    /// The move that undoes this one.
+    pub fn inverse(self) -> ActionType {
+        match self {
+            ActionType::None => ActionType::None,
+            ActionType::Up => ActionType::Down,
+            ActionType::Down => ActionType::Up,
+            ActionType::Left => ActionType::Right,
+            ActionType::Right => ActionType::Left,
+        }
+    }
+
+    /// One letter for the move: U, D, L or R, and '-' for none.
+    pub fn to_char(self) -> char {
+        match self {
+            ActionType::None => '-',
+            ActionType::Up => 'U',
+            ActionType::Down => 'D',
+            ActionType::Left => 'L',
+            ActionType::Right => 'R',
+        }
+    }
+    /// Reads a letter written by `to_char`.
+    pub fn from_char(ch: char) -> Option<ActionType> {
+        match ch.to_ascii_uppercase() {
+            '-' => Some(ActionType::None),
+            'U' => Some(ActionType::Up),
+            'D' => Some(ActionType::Down),
+            'L' => Some(ActionType::Left),
+            'R' => Some(ActionType::Right),
+            _ => None,
+        }
+    }
+}
+
+/// A tile's number, with 0 as the blank. Wide enough for any board that fits in memory.
+pub type Tile = u32;
+
+/// A board of any rows by columns shape. Moves are named for the direction the
+/// blank travels: `Up` takes it to the row above (x - 1), `Left` to the column
+/// before (y - 1).
 #[derive(Clone, PartialEq, Hash, Debug)]
 pub struct Puzzle {
-    map: Array2<u8>,  // Starting state, for reference.
-    blank: Vector2,     // Location of the blank spot.
-    dimension: Vector2, // Size of the grid.
+    map: Array2<Tile>,  // Starting state, for reference.
+    blank: Vector2,     // Location of the blank spot, as (row, column).
+    dimension: Vector2, // Size of the grid, as (rows, columns).
 }
 impl Eq for Puzzle {}
 
 impl Puzzle {
     // NEW
+    /// A random layout. Panics if either side of the board is zero, see `try_new`.
     pub fn new(dimension: Vector2) -> Puzzle {
+        Puzzle::try_new(dimension).expect("Invalid puzzle dimension.")
+    }
+    /// A random layout, or an error if either side of the board is zero.
+    pub fn try_new(dimension: Vector2) -> Result<Puzzle, PuzzleError> {
+        cell_count(dimension)?;
         let map = random(dimension);
-        let blank = match find_value(&map, 0) {
-            Some(vec) => vec,
-            None => panic!("Can't find blank."),
-        };
+        let blank = find_value(&map, 0).ok_or(PuzzleError::MissingBlank)?;
 
-        Puzzle { map, blank, dimension }
-    } 
-    pub fn from_vec(dimension: Vector2, vec: Vec<u8>) -> Puzzle {
-        let shape = dimension.dim();
-        let map = Array2::<u8>::from_shape_vec(shape, vec).unwrap();
-        let blank = find_value(&map, 0).unwrap();
+        Ok(Puzzle { map, blank, dimension })
+    }
+    /// Builds a puzzle from row-major tiles. Panics if the board is invalid,
+    /// so prefer `try_from_vec` for boards from users or files.
+    pub fn from_vec(dimension: Vector2, vec: Vec<Tile>) -> Puzzle {
+        Puzzle::try_from_vec(dimension, vec).expect("Invalid puzzle.")
+    }
+    /// Builds a puzzle from row-major tiles, which must be each of 0..rows*columns once.
+    pub fn try_from_vec(dimension: Vector2, vec: Vec<Tile>) -> Result<Puzzle, PuzzleError> {
+        let expected = cell_count(dimension)?;
+        let found = vec.len();
+        if found != expected {
+            return Err(PuzzleError::WrongLength { expected, found });
+        }
+        if !vec.contains(&0) {
+            return Err(PuzzleError::MissingBlank);
+        }
+        let mut seen = vec![false; expected];
+        for tile in &vec {
+            match seen.get_mut(*tile as usize) {
+                None => return Err(PuzzleError::OutOfRange(*tile)),
+                Some(true) => return Err(PuzzleError::DuplicateTile(*tile)),
+                Some(spot) => *spot = true,
+            }
+        }
+
+        let map = Array2::<Tile>::from_shape_vec(dimension.dim(), vec)
+            .map_err(|_| PuzzleError::WrongLength { expected, found })?;
+        let blank = find_value(&map, 0).ok_or(PuzzleError::MissingBlank)?;
+        Ok(Puzzle { map, blank, dimension })
+    }
+    /// Rebuilds a board from tiles that came out of a `PackedBoard`, skipping validation.
+    pub(crate) fn from_packed(dimension: Vector2, vec: Vec<Tile>) -> Puzzle {
+        let map = Array2::<Tile>::from_shape_vec(dimension.dim(), vec).expect("Packed board has the wrong size.");
+        let blank = find_value(&map, 0).expect("Packed board has no blank.");
         Puzzle { map, blank, dimension }
     }
+    /// The layout with the given `rank`, the inverse of `Puzzle::rank`.
+    pub fn from_rank(dimension: Vector2, rank: u128) -> Result<Puzzle, PuzzleError> {
+        let cells = cell_count(dimension)?;
+        match Puzzle::orderings(dimension) {
+            Some(count) if rank < count => {},
+            _ => return Err(PuzzleError::RankOutOfRange(rank)),
+        }
+        let mut digits = vec![0; cells];
+        let mut rest = rank;
+        for i in (0..cells).rev() {
+            let base = (cells - i) as u128;
+            digits[i] = (rest % base) as usize;
+            rest /= base;
+        }
+        let mut free: Vec<Tile> = (0..cells as Tile).collect();
+        Puzzle::try_from_vec(dimension, digits.into_iter().map(|digit| free.remove(digit)).collect())
+    }
+    /// The solved layout: tiles in order with the blank in the last spot.
+    /// Panics if the dimension is invalid, see `try_goal`.
+    pub fn goal(dimension: Vector2) -> Puzzle {
+        Puzzle::try_goal(dimension).expect("Invalid puzzle dimension.")
+    }
+    /// The solved layout, or an error if the board has no cells or too many.
+    pub fn try_goal(dimension: Vector2) -> Result<Puzzle, PuzzleError> {
+        let total = cell_count(dimension)? as Tile;
+        let mut vec: Vec<Tile> = (1..total).collect();
+        vec.push(0);
+        Puzzle::try_from_vec(dimension, vec)
+    }
+
+    // Accessors
+    pub fn dimension(&self) -> Vector2 {
+        self.dimension
+    }
+    pub fn blank(&self) -> Vector2 {
+        self.blank
+    }
+    /// The tiles in row-major order, with 0 as the blank.
+    pub fn tiles(&self) -> Vec<Tile> {
+        self.map.iter().copied().collect()
+    }
+    /// A compact copy of the board for hashing and storage.
+    pub fn pack(&self) -> PackedBoard {
+        match self.map.as_slice() {
+            Some(tiles) => PackedBoard::from_tiles(tiles),
+            None => PackedBoard::from_tiles(&self.tiles()),
+        }
+    }
+
+    // Ranking
+    /// Number of layouts of a board this size, (rows * columns)!, or `None` when
+    /// that doesn't fit in a `u128` (more than 34 cells). Only half of them are
+    /// reachable from any one layout when the board has two or more rows and columns.
+    pub fn orderings(dimension: Vector2) -> Option<u128> {
+        let cells = cell_count(dimension).ok()?;
+        (1..=cells as u128).try_fold(1u128, |count, n| count.checked_mul(n))
+    }
+    /// A perfect hash of the layout: its position, from 0, among every ordering of the
+    /// tiles sorted by their row-major reading (the Lehmer code). `None` for boards
+    /// too large to rank, see `Puzzle::orderings`.
+    pub fn rank(&self) -> Option<u128> {
+        Puzzle::orderings(self.dimension)?;
+        let tiles = self.tiles();
+        let mut used = vec![false; tiles.len()];
+        let mut rank = 0;
+        for (i, tile) in tiles.iter().enumerate() {
+            let smaller = used[..*tile as usize].iter().filter(|used| !**used).count();
+            used[*tile as usize] = true;
+            rank = rank * (tiles.len() - i) as u128 + smaller as u128;
+        }
+        Some(rank)
+    }
 
     /// Returns a cloned version of the puzzle changed by the given move.
     pub fn act(&self, action: ActionType) -> Puzzle {
@@ -66,6 +215,36 @@ impl Puzzle {
         puzzle
     }
 
+    /// The single move that turns this puzzle into `next`, if there is one.
+    pub fn action_to(&self, next: &Puzzle) -> Option<ActionType> {
+        ActionType::ALL.into_iter()
+            .find(|action| self.blank != next.blank && self.act(*action) == *next)
+    }
+
+    /// The moves that keep the blank on the board.
+    pub fn legal_actions(&self) -> Vec<ActionType> {
+        let (blank, dimension) = (self.blank, self.dimension);
+        ActionType::ALL.into_iter()
+            .filter(|action| match action {
+                ActionType::Up => blank.x > 0,
+                ActionType::Down => blank.x < dimension.x - 1,
+                ActionType::Left => blank.y > 0,
+                ActionType::Right => blank.y < dimension.y - 1,
+                ActionType::None => false,
+            })
+            .collect()
+    }
+
+    /// The state after each legal move, leaving out the move that would undo
+    /// `previous`. Pass `ActionType::None` to keep every move.
+    pub fn successors(&self, previous: ActionType) -> Vec<(ActionType, Puzzle)> {
+        let undo = previous.inverse();
+        self.legal_actions().into_iter()
+            .filter(|action| previous == ActionType::None || *action != undo)
+            .map(|action| (action, self.act(action)))
+            .collect()
+    }
+
     /// Updates the position of the blank tile.
     fn move_blank(&mut self, direction: Vector2) {
         if direction.x == 0 && direction.y == 0 {
@@ -85,19 +264,19 @@ impl Puzzle {
         self.write_at_pos(pos1, self.read_at_pos(pos2));
         self.write_at_pos(pos2, val)
     }
-    fn write_at_pos(&mut self, pos: Vector2, val: u8) {
+    fn write_at_pos(&mut self, pos: Vector2, val: Tile) {
         self.map[[pos.x as usize, pos.y as usize]] = val;
     }
-    pub fn read_at_pos(&self, pos: Vector2) -> u8 {
+    pub fn read_at_pos(&self, pos: Vector2) -> Tile {
         self.map[[pos.x as usize, pos.y as usize]]
     }
     
     pub fn print(&self, label: &str) {
-        println!("{}\n{}", label, self.to_string());
+        println!("{}\n{}", label, self);
     }
 
     /// A heuristic that counts the number of misplaced tiles.
-    pub fn heuristic_misplaced(&self, goal: &Puzzle) -> u8 {
+    pub fn heuristic_misplaced(&self, goal: &Puzzle) -> u32 {
         let mut count = 0;
         for spot in self.map.iter().zip(goal.map.iter()) {
             if *spot.0 != *spot.1 && *spot.0 != 0 {
@@ -109,19 +288,58 @@ impl Puzzle {
 
     /// A heuristic that sums the distances of each tile from its goal.
     pub fn heuristic_distances(&self, goal: &Puzzle) -> u32 {
+        let mut targets = vec![None; self.map.len()];
+        for ((i, j), value) in goal.map.indexed_iter() {
+            if let Some(target) = targets.get_mut(*value as usize) {
+                *target = Some(Vector2::new(i as i32, j as i32));
+            }
+        }
+
         let mut count = 0;
-        for (i, row) in self.map.rows().into_iter().enumerate() {
-            for (j, col) in row.into_iter().enumerate() {
-                if *col != 0 {
-                    let position = Vector2::new(i as i32, j as i32);
-                    let other = find_value(&goal.map, *col).unwrap();
-                    count += position.distance_ortho(&other);
-                } 
+        for ((i, j), value) in self.map.indexed_iter() {
+            if *value != 0 {
+                let position = Vector2::new(i as i32, j as i32);
+                let other = targets[*value as usize].unwrap();
+                count += position.distance_ortho(&other);
             }
         }
         count
     }
 
+    /// Manhattan distance plus 2 for every tile that must leave its goal row or
+    /// column to let another tile past. For each line the fewest tiles that have to
+    /// step aside is the line length minus its longest in-order run, so the
+    /// estimate never exceeds the true number of moves.
+    pub fn heuristic_linear_conflict(&self, goal: &Puzzle) -> u32 {
+        let mut targets = vec![None; self.map.len()];
+        for ((i, j), value) in goal.map.indexed_iter() {
+            if let Some(target) = targets.get_mut(*value as usize) {
+                *target = Some((i, j));
+            }
+        }
+        let target = |value: Tile| targets.get(value as usize).copied().flatten();
+
+        let mut conflicts = 0;
+        // Rows: tiles already in their goal row, listed by their goal column.
+        for (i, row) in self.map.rows().into_iter().enumerate() {
+            let line: Vec<usize> = row.iter()
+                .filter(|value| **value != 0)
+                .filter_map(|value| target(*value).filter(|t| t.0 == i).map(|t| t.1))
+                .collect();
+            conflicts += line.len() - longest_increasing(&line);
+        }
+        // Columns: tiles already in their goal column, listed by their goal row.
+        for (j, column) in self.map.columns().into_iter().enumerate() {
+            let line: Vec<usize> = column.iter()
+                .filter(|value| **value != 0)
+                .filter_map(|value| target(*value).filter(|t| t.1 == j).map(|t| t.0))
+                .collect();
+            conflicts += line.len() - longest_increasing(&line);
+        }
+
+        self.heuristic_distances(goal) + 2 * conflicts as u32
+    }
+
     /// Finds the number of inversions in the puzzle layout.
     pub fn inversions(&self) -> u32 {
         let vec = self.map.clone().into_raw_vec();
@@ -143,17 +361,70 @@ impl Puzzle {
         counter
     }
 
-    /// Uses inversions to test if this puzzle is solvable.
+    /// Tests if this puzzle can reach the standard goal from `Puzzle::goal`.
     pub fn test_solvable(&self) -> bool {
-        self.inversions() % 2 == 0
+        self.is_solvable_to(&Puzzle::goal(self.dimension))
+    }
+
+    /// Tests if this puzzle can be slid into the given goal, for any board shape.
+    /// Every move swaps the blank with a neighbour, flipping the parity of the
+    /// layout's permutation and of the blank's distance from its goal spot, so
+    /// the two parities must match. On a single row or column the tiles can't
+    /// pass each other at all, so their order has to match instead.
+    pub fn is_solvable_to(&self, goal: &Puzzle) -> bool {
+        if self.dimension != goal.dimension {
+            return false;
+        }
+        let tiles = self.tiles();
+        let goal_tiles = goal.tiles();
+
+        // Where each tile sits on the goal board; both boards need the same distinct tiles.
+        let mut target = vec![None; tiles.len()];
+        for (index, tile) in goal_tiles.iter().enumerate() {
+            match target.get_mut(*tile as usize) {
+                Some(spot @ None) => *spot = Some(index),
+                _ => return false,
+            }
+        }
+        let mut permutation = Vec::with_capacity(tiles.len());
+        for tile in &tiles {
+            match target.get_mut(*tile as usize).and_then(Option::take) {
+                Some(index) => permutation.push(index),
+                None => return false,
+            }
+        }
+
+        if self.dimension.x == 1 || self.dimension.y == 1 {
+            let order = |tiles: &[Tile]| tiles.iter().filter(|tile| **tile != 0).copied().collect::<Vec<Tile>>();
+            return order(&tiles) == order(&goal_tiles);
+        }
+
+        // A permutation is odd when it has an odd number of even-length cycles.
+        let mut seen = vec![false; permutation.len()];
+        let mut odd = false;
+        for start in 0..permutation.len() {
+            let mut length = 0;
+            let mut index = start;
+            while !seen[index] {
+                seen[index] = true;
+                index = permutation[index];
+                length += 1;
+            }
+            if length > 0 && length % 2 == 0 {
+                odd = !odd;
+            }
+        }
+        let blank_odd = self.blank.distance_ortho(&goal.blank) % 2 == 1;
+        odd == blank_odd
     }
 }
 
-impl ToString for Puzzle {
+impl fmt::Display for Puzzle {
     // Produces a string version of the map.
-    fn to_string(&self) -> String {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut str = String::new();
-        let total = self.dimension.x * self.dimension.y;
+        // Pad every tile to the width of the largest one.
+        let width = (self.map.len() - 1).to_string().len();
 
         for i in self.map.rows() {
             for j in i {
@@ -161,34 +432,60 @@ impl ToString for Puzzle {
                     0 => String::from("_"),
                     _ => j.to_string(),
                 };
-                let st = match total {
-                    v if v >=  10 => format!("{:>2} ", ch),
-                    v if v >= 100 => format!("{:>3} ", ch),
-                    _ => format!("{} ", ch),
-                };
+                let st = format!("{:>width$} ", ch, width = width);
 
                 str.push_str(&st);
             }
             str.push('\n');
         }
 
-        str
+        f.write_str(&str)
+    }
+}
+
+impl TryFrom<(Vector2, Vec<Tile>)> for Puzzle {
+    type Error = PuzzleError;
+
+    fn try_from((dimension, vec): (Vector2, Vec<Tile>)) -> Result<Self, Self::Error> {
+        Puzzle::try_from_vec(dimension, vec)
+    }
+}
+
+/// The number of cells on a board, or an error if a side isn't positive or the
+/// count doesn't fit in an `i32`.
+fn cell_count(dimension: Vector2) -> Result<usize, PuzzleError> {
+    if dimension.x <= 0 || dimension.y <= 0 {
+        return Err(PuzzleError::ZeroDimension);
     }
+    dimension.x.checked_mul(dimension.y).map(|cells| cells as usize).ok_or(PuzzleError::TooLarge)
 }
 
 /// Creates a random array with one of each value, from 0..x*y
-fn random(dimension: Vector2) -> Array2<u8> {
-    let range = 0..(dimension.x.abs() * dimension.y.abs()) as u8;
-    let mut vec: Vec<u8> = range.collect();
+fn random(dimension: Vector2) -> Array2<Tile> {
+    let range = 0..(dimension.x.abs() * dimension.y.abs()) as Tile;
+    let mut vec: Vec<Tile> = range.collect();
     vec.shuffle(&mut thread_rng());
 
     let shape = dimension.dim();
-    Array2::<u8>::from_shape_vec(shape, vec)
-        .unwrap_or_else(|_|Array2::<u8>::zeros(shape))
+    Array2::<Tile>::from_shape_vec(shape, vec)
+        .unwrap_or_else(|_|Array2::<Tile>::zeros(shape))
+}
+
+/// Length of the longest strictly increasing subsequence.
+fn longest_increasing(line: &[usize]) -> usize {
+    let mut tails: Vec<usize> = Vec::new();
+    for value in line {
+        match tails.binary_search(value) {
+            Ok(_) => {},
+            Err(index) if index == tails.len() => tails.push(*value),
+            Err(index) => tails[index] = *value,
+        }
+    }
+    tails.len()
 }
 
 /// If it exists, finds the position of the given value on the map.
-fn find_value(map: &Array2<u8>, value: u8) -> Option<Vector2> {
+fn find_value(map: &Array2<Tile>, value: Tile) -> Option<Vector2> {
     for (i, row) in map.rows().into_iter().enumerate() {
         for (j, col) in row.into_iter().enumerate() {
             if *col == value {
@@ -2,7 +2,7 @@
 use std::cmp::Reverse;
 use priority_queue::PriorityQueue;
 use slab_tree::{NodeId, TreeBuilder};
-use crate::{puzzle::*, agent::Path, vector::Vector2};
+use crate::{puzzle::*, agent::*, error::*, pdb::*, packed::PackedBoard, observer::*, vector::Vector2};
 
 
 #[test]
@@ -22,6 +22,26 @@ fn heuristics() {
     assert_eq!(goal.heuristic_distances(&goal), 0);
     assert_eq!(puzzle.heuristic_misplaced(&goal), 7);
     assert_eq!(puzzle.heuristic_distances(&goal), 14);
+    assert_eq!(goal.heuristic_linear_conflict(&goal), 0);
+    // No tile shares its goal line with a tile it has to pass.
+    assert_eq!(puzzle.heuristic_linear_conflict(&goal), 14);
+}
+
+#[test]
+fn linear_conflict() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    let puzzle = Puzzle::from_vec(dimension, vec![1, 2, 3, 4, 6, 5, 7, 8, 0]);
+    assert_eq!(puzzle.heuristic_linear_conflict(&goal), 4);
+    // Three tiles fully reversed in one row only need two of them to step aside.
+    let puzzle = Puzzle::from_vec(dimension, vec![3, 2, 1, 4, 5, 6, 7, 8, 0]);
+    assert_eq!(puzzle.heuristic_distances(&goal), 4);
+    assert_eq!(puzzle.heuristic_linear_conflict(&goal), 8);
+
+    let puzzle = Puzzle::from_vec(dimension, vec![4, 1, 8, 7, 0, 5, 3, 6, 2]);
+    let mut agent = Agent::new(puzzle, goal).unwrap();
+    let solution = agent.solve_optimal(Heuristic::LinearConflict, 1_000_000).unwrap();
+    assert_eq!(solution.steps, 20);
 }
 
 #[test]
@@ -64,7 +84,7 @@ fn priority() {
         4, 5, 0, 
         7, 8, 6]);
 
-    let root = Path::new(goal.clone(), ActionType::None, 0);
+    let root = Path::new(goal.clone(), ActionType::None, 0, 0);
     let mut tree = 
         TreeBuilder::<Path>::new()
         .with_root(root)
@@ -76,7 +96,7 @@ fn priority() {
     
     let distance = puzzle2.heuristic_distances(&goal);
     let mut root = tree.root_mut().expect("msg");
-    let node = root.append(Path::new(puzzle2.clone(), ActionType::None, puzzle2.heuristic_distances(&goal)));
+    let node = root.append(Path::new(puzzle2.clone(), ActionType::None, 1, puzzle2.heuristic_distances(&goal)));
     frontier.push(node.node_id(), Reverse(distance));
 
     let pop1 = frontier.pop().unwrap();
@@ -85,3 +105,723 @@ fn priority() {
     assert_eq!(frontier.pop().unwrap().1, Reverse(14));
 
 }
+
+#[test]
+fn solvable() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::from_vec(dimension, vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    let homework = Puzzle::from_vec(dimension, vec![4, 5, 0, 6, 1, 8, 7, 3, 2]);
+    let trivial = Puzzle::from_vec(dimension, vec![4, 1, 3, 0, 2, 6, 7, 5, 8]);
+
+    assert!(goal.test_solvable());
+    assert!(!homework.test_solvable());
+    assert!(trivial.test_solvable());
+    assert_eq!(Puzzle::goal(dimension), goal);
+    assert!(trivial.is_solvable_to(&goal) && goal.is_solvable_to(&trivial));
+    assert!(!homework.is_solvable_to(&trivial));
+    // Against an arbitrary goal the parity of both boards matters, not just the start.
+    assert!(homework.is_solvable_to(&Puzzle::from_vec(dimension, vec![4, 5, 8, 6, 1, 0, 7, 3, 2])));
+    assert!(!goal.is_solvable_to(&Puzzle::from_vec(dimension, vec![1, 2, 3, 4, 5, 6, 8, 7, 0])));
+
+    // Even width: the blank's row has to be accounted for.
+    let dimension = Vector2::new(4, 4);
+    let goal = Puzzle::goal(dimension);
+    let up = goal.act(ActionType::Up);
+    assert!(up.test_solvable());
+    assert!(up.act(ActionType::Left).act(ActionType::Up).is_solvable_to(&goal));
+    let mut tiles = goal.tiles();
+    tiles.swap(0, 1);
+    assert!(!Puzzle::from_vec(dimension, tiles).test_solvable());
+
+    // Single row: tiles can only shuffle the blank around.
+    let dimension = Vector2::new(1, 4);
+    let goal = Puzzle::goal(dimension);
+    assert!(Puzzle::from_vec(dimension, vec![1, 0, 2, 3]).is_solvable_to(&goal));
+    assert!(!Puzzle::from_vec(dimension, vec![2, 1, 3, 0]).is_solvable_to(&goal));
+
+    // Different sizes or tile sets never match.
+    assert!(!goal.is_solvable_to(&Puzzle::goal(Vector2::new(2, 2))));
+}
+
+#[test]
+fn astar_optimal() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    // Known to be 20 moves from the goal.
+    let puzzle = Puzzle::from_vec(dimension, vec![4, 1, 8, 7, 0, 5, 3, 6, 2]);
+
+    for heuristic in [Heuristic::Misplaced, Heuristic::OrthoDistance] {
+        let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+        let solution = agent.uniform_cost_search(SearchStrategy::AStar, heuristic, 1_000_000).unwrap();
+        assert_eq!(solution.steps, 20);
+    }
+}
+
+#[test]
+fn optimal_certificate() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    let puzzle = Puzzle::from_vec(dimension, vec![4, 1, 8, 7, 0, 5, 3, 6, 2]);
+
+    let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+    let solution = agent.solve_optimal(Heuristic::OrthoDistance, 1_000_000).unwrap();
+    assert!(solution.is_optimal());
+    assert_eq!(solution.certificate.unwrap().lower_bound, 20);
+
+    let mut agent = Agent::new(puzzle, goal).unwrap();
+    let result = agent.solve_optimal(Heuristic::Inversions, 1_000_000);
+    assert_eq!(result.err(), Some(SearchError::InadmissibleHeuristic(Heuristic::Inversions)));
+}
+
+#[test]
+fn ida_star() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    let puzzle = Puzzle::from_vec(dimension, vec![4, 1, 8, 7, 0, 5, 3, 6, 2]);
+    let mut agent = Agent::new(puzzle, goal.clone()).unwrap();
+    let solution = agent.uniform_cost_search(SearchStrategy::IDAStar, Heuristic::OrthoDistance, 1_000_000).unwrap();
+    assert_eq!(solution.steps, 20);
+    assert_eq!(solution.state_path[20], goal);
+
+    // A 4x4 board 24 moves from the goal.
+    let dimension = Vector2::new(4, 4);
+    let goal = Puzzle::goal(dimension);
+    let puzzle = Puzzle::from_vec(dimension, vec![
+        0, 2, 5, 7,
+        9, 1, 4, 6,
+        13, 10, 11, 3,
+        14, 15, 12, 8]);
+    let mut agent = Agent::new(puzzle, goal).unwrap();
+    let solution = agent.uniform_cost_search(SearchStrategy::IDAStar, Heuristic::OrthoDistance, 10_000_000).unwrap();
+    assert_eq!(solution.steps, 24);
+}
+
+#[test]
+fn uninformed() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    // Known to be 12 moves from the goal.
+    let puzzle = Puzzle::from_vec(dimension, vec![5, 4, 1, 7, 3, 2, 0, 8, 6]);
+    for search_strategy in [SearchStrategy::BreadthFirst, SearchStrategy::UniformCost] {
+        let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+        let solution = agent.uniform_cost_search(search_strategy, Heuristic::Misplaced, 1_000_000).unwrap();
+        assert_eq!(solution.steps, 12);
+    }
+
+    let trivial = Puzzle::from_vec(dimension, vec![4, 1, 3, 0, 2, 6, 7, 5, 8]);
+    let mut agent = Agent::new(trivial.clone(), goal.clone()).unwrap();
+    let result = agent.uniform_cost_search(SearchStrategy::DepthLimited(4), Heuristic::Misplaced, 1_000_000);
+    assert_eq!(result.err(), Some(SearchError::NoSolution));
+    let mut agent = Agent::new(trivial, goal).unwrap();
+    let solution = agent.uniform_cost_search(SearchStrategy::DepthLimited(5), Heuristic::Misplaced, 1_000_000).unwrap();
+    assert_eq!(solution.steps, 5);
+}
+
+#[test]
+fn bidirectional() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    let puzzle = Puzzle::from_vec(dimension, vec![4, 1, 8, 7, 0, 5, 3, 6, 2]);
+    for search_strategy in [SearchStrategy::Bidirectional, SearchStrategy::BidirectionalMM] {
+        let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+        let solution = agent.uniform_cost_search(search_strategy, Heuristic::OrthoDistance, 1_000_000).unwrap();
+        assert_eq!(solution.steps, 20);
+        assert_eq!(solution.state_path.len(), 21);
+        assert_eq!(solution.state_path[0], puzzle);
+        assert_eq!(solution.state_path[20], goal);
+        for pair in solution.state_path.windows(2) {
+            assert_eq!(pair[0].heuristic_misplaced(&pair[1]), 1);
+        }
+    }
+}
+
+#[test]
+fn weighted() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    let puzzle = Puzzle::from_vec(dimension, vec![4, 1, 8, 7, 0, 5, 3, 6, 2]);
+
+    let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+    let solution = agent.uniform_cost_search(SearchStrategy::WeightedAStar(1.5), Heuristic::OrthoDistance, 1_000_000).unwrap();
+    assert!(solution.steps >= 20 && solution.steps <= 30);
+
+    let mut agent = Agent::new(puzzle, goal.clone()).unwrap();
+    let solutions = agent.anytime_search(Heuristic::OrthoDistance, 3.0, 1_000_000).unwrap();
+    assert!(!solutions.is_empty());
+    for pair in solutions.windows(2) {
+        assert!(pair[1].steps < pair[0].steps);
+    }
+    for solution in &solutions {
+        assert!(solution.certificate.unwrap().lower_bound <= 20);
+        assert!(solution.suboptimality().unwrap() <= 3.0);
+    }
+    let last = solutions.last().unwrap();
+    assert_eq!(last.steps, 20);
+    assert!(last.is_optimal());
+
+    for weight in [0.5, -1.0, f32::NAN, f32::INFINITY] {
+        assert_eq!(agent.anytime_search(Heuristic::OrthoDistance, weight, 1_000).err().map(|e| e.to_string()),
+            Some(SearchError::InvalidWeight(weight).to_string()));
+        assert!(matches!(agent.uniform_cost_search(SearchStrategy::WeightedAStar(weight), Heuristic::OrthoDistance, 1_000),
+            Err(SearchError::InvalidWeight(_))));
+    }
+    assert!("weighted-astar:nan".parse::<SearchStrategy>().is_err());
+
+    // Running out before any solution is an error, running out after one is reported beside it.
+    assert_eq!(agent.anytime_search(Heuristic::OrthoDistance, 3.0, 10).err(), Some(SearchError::BudgetExhausted));
+    let first = agent.anytime_search(Heuristic::OrthoDistance, 3.0, 1_000_000).unwrap()[0].stats.expanded;
+    assert_eq!(agent.stop_reason(), None);
+    assert_eq!(agent.anytime_search(Heuristic::OrthoDistance, 3.0, first).unwrap().len(), 1);
+    assert_eq!(agent.stop_reason(), Some(SearchError::BudgetExhausted));
+    let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+    agent.set_limits(SearchLimits { cancel: Some(cancel), ..SearchLimits::default() });
+    assert_eq!(agent.anytime_search(Heuristic::OrthoDistance, 3.0, 1_000_000).err(), Some(SearchError::Cancelled));
+    // A huge weight saturates the priority instead of overflowing it.
+    let mut agent = Agent::new(Puzzle::from_vec(dimension, vec![4, 1, 8, 7, 0, 5, 3, 6, 2]), goal).unwrap();
+    assert!(agent.uniform_cost_search(SearchStrategy::WeightedAStar(1e30), Heuristic::OrthoDistance, 1_000_000).is_ok());
+}
+
+#[test]
+fn anytime_bound() {
+    // Only tiles 1 and 2 count, so the start looks finished (f = 0) while every
+    // child has f of at least 1: the goal turns up while the parent's f is below
+    // anything left in the frontier.
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    let puzzle = Puzzle::from_vec(dimension, vec![1, 2, 3, 4, 5, 6, 7, 0, 8]);
+    let mut agent = Agent::new(puzzle, goal.clone()).unwrap();
+    agent.set_pattern_database(std::sync::Arc::new(PatternDatabase::build(&goal, &[vec![1, 2]]).unwrap()));
+
+    // One expansion finds the goal but can't yet prove nothing shorter is left.
+    let solutions = agent.anytime_search(Heuristic::PatternDatabase, 5.0, 1).unwrap();
+    assert_eq!(solutions.len(), 1);
+    assert_eq!(solutions[0].steps, 1);
+    assert_eq!(solutions[0].certificate.unwrap().lower_bound, 0);
+
+    let solutions = agent.anytime_search(Heuristic::PatternDatabase, 5.0, 1_000_000).unwrap();
+    for solution in &solutions {
+        assert!(solution.certificate.unwrap().lower_bound <= 1);
+    }
+    assert!(solutions.last().unwrap().is_optimal());
+}
+
+#[test]
+fn pattern_database() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    let partition = vec![vec![1, 2, 3, 4], vec![5, 6, 7, 8]];
+    let database = PatternDatabase::build(&goal, &partition).unwrap();
+    assert_eq!(database.heuristic(&goal), 0);
+
+    // Each pattern has to move its tiles at least as far as their orthogonal distance.
+    let puzzle = Puzzle::from_vec(dimension, vec![4, 1, 8, 7, 0, 5, 3, 6, 2]);
+    let h = database.heuristic(&puzzle);
+    assert!(h >= puzzle.heuristic_distances(&goal) && h <= 20);
+
+    let path = std::env::temp_dir().join(format!("sliding-puzzle-test-{}.pdb", std::process::id()));
+    database.save(&path).unwrap();
+    assert_eq!(PatternDatabase::load(&path).unwrap(), database);
+    std::fs::write(&path, b"SPDB\x01").unwrap();
+    assert!(matches!(PatternDatabase::load(&path), Err(PatternDatabaseError::Corrupt(_))));
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(PatternDatabase::build(&goal, &[vec![1, 2], vec![2, 3]]).is_err());
+
+    // Without a database for the goal the heuristic is refused, not swapped for another.
+    let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+    assert_eq!(agent.solve_optimal(Heuristic::PatternDatabase, 1_000_000).err(), Some(SearchError::MissingPatternDatabase));
+    let other_goal = Puzzle::from_vec(dimension, vec![0, 1, 2, 3, 4, 5, 6, 7, 8]);
+    agent.set_pattern_database(std::sync::Arc::new(PatternDatabase::build(&other_goal, &partition).unwrap()));
+    assert_eq!(agent.solve_optimal(Heuristic::PatternDatabase, 1_000_000).err(), Some(SearchError::MissingPatternDatabase));
+    assert!(agent.anytime_search(Heuristic::PatternDatabase, 2.0, 1_000_000).is_err());
+    assert!(agent.uniform_cost_search(SearchStrategy::BreadthFirst, Heuristic::PatternDatabase, 1_000_000).is_ok());
+
+    agent.set_pattern_database(std::sync::Arc::new(database));
+    let solution = agent.solve_optimal(Heuristic::PatternDatabase, 1_000_000).unwrap();
+    assert_eq!(solution.steps, 20);
+    // MM also estimates back towards the start, which the database wasn't built for.
+    assert_eq!(agent.uniform_cost_search(SearchStrategy::BidirectionalMM, Heuristic::PatternDatabase, 1_000_000).err(),
+        Some(SearchError::MissingPatternDatabase));
+}
+
+#[test]
+fn rejects_bad_pairs() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    let homework = Puzzle::from_vec(dimension, vec![4, 5, 0, 6, 1, 8, 7, 3, 2]);
+    let check = |initial: Puzzle, goal: Puzzle| Agent::new(initial, goal).err();
+
+    assert_eq!(check(homework, goal.clone()), Some(SearchError::Unsolvable));
+    assert_eq!(check(Puzzle::goal(Vector2::new(2, 2)), goal.clone()), Some(SearchError::DimensionMismatch));
+
+    let puzzle = Puzzle::from_vec(dimension, vec![4, 1, 8, 7, 0, 5, 3, 6, 2]);
+    let mut agent = Agent::new(puzzle, goal).unwrap();
+    let result = agent.uniform_cost_search(SearchStrategy::AStar, Heuristic::Misplaced, 10);
+    assert_eq!(result.err(), Some(SearchError::BudgetExhausted));
+}
+
+#[test]
+fn fallible_constructors() {
+    let dimension = Vector2::new(3, 3);
+    let check = |dimension, vec| Puzzle::try_from_vec(dimension, vec).err();
+
+    assert_eq!(check(dimension, vec![1, 2, 3, 4, 5, 6, 7, 8, 0]), None);
+    assert_eq!(check(dimension, vec![1, 2, 3, 0]), Some(PuzzleError::WrongLength { expected: 9, found: 4 }));
+    assert_eq!(check(dimension, vec![1, 1, 3, 4, 5, 6, 7, 8, 0]), Some(PuzzleError::DuplicateTile(1)));
+    assert_eq!(check(dimension, vec![1, 2, 3, 4, 5, 6, 7, 9, 0]), Some(PuzzleError::OutOfRange(9)));
+    assert_eq!(check(dimension, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]), Some(PuzzleError::MissingBlank));
+    assert_eq!(check(Vector2::new(0, 3), vec![]), Some(PuzzleError::ZeroDimension));
+    assert_eq!(Puzzle::try_new(Vector2::new(3, 0)).err(), Some(PuzzleError::ZeroDimension));
+    // 70000 * 70000 overflows an i32, so the cell count can't be trusted.
+    let huge = Vector2::new(70_000, 70_000);
+    assert_eq!(check(huge, vec![0]), Some(PuzzleError::TooLarge));
+    assert_eq!(Puzzle::try_new(huge).err(), Some(PuzzleError::TooLarge));
+    assert_eq!(Puzzle::try_goal(huge).err(), Some(PuzzleError::TooLarge));
+    assert_eq!(Puzzle::from_rank(huge, 0).err(), Some(PuzzleError::TooLarge));
+    assert_eq!(Puzzle::orderings(huge), None);
+
+    let puzzle = Puzzle::try_from((dimension, vec![4, 1, 3, 0, 2, 6, 7, 5, 8])).unwrap();
+    assert_eq!(puzzle.blank(), Vector2::new(1, 0));
+}
+
+#[test]
+fn solution_moves() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    let trivial = Puzzle::from_vec(dimension, vec![4, 1, 3, 0, 2, 6, 7, 5, 8]);
+
+    for search_strategy in [SearchStrategy::AStar, SearchStrategy::IDAStar, SearchStrategy::Bidirectional] {
+        let mut agent = Agent::new(trivial.clone(), goal.clone()).unwrap();
+        let solution = agent.uniform_cost_search(search_strategy, Heuristic::OrthoDistance, 1_000).unwrap();
+        assert_eq!(solution.moves(), "URDDR");
+        assert_eq!(solution.actions.len(), solution.steps as usize);
+
+        let mut puzzle = trivial.clone();
+        for action in &solution.actions {
+            puzzle = puzzle.act(*action);
+        }
+        assert_eq!(puzzle, goal);
+    }
+    assert_eq!(ActionType::from_char('u'), Some(ActionType::Up));
+    assert_eq!(trivial.action_to(&trivial.act(ActionType::Right)), Some(ActionType::Right));
+    assert_eq!(trivial.action_to(&trivial), None);
+}
+
+#[test]
+fn solution_order() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    let puzzle = Puzzle::from_vec(dimension, vec![5, 4, 1, 7, 3, 2, 0, 8, 6]);
+
+    for search_strategy in [SearchStrategy::AStar, SearchStrategy::BreadthFirst, SearchStrategy::IDAStar, SearchStrategy::BidirectionalMM] {
+        let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+        let solution = agent.uniform_cost_search(search_strategy, Heuristic::OrthoDistance, 1_000_000).unwrap();
+        assert_eq!(solution.state_path.first(), Some(&puzzle));
+        assert_eq!(solution.state_path.last(), Some(&goal));
+        assert_eq!(solution.steps, 12);
+        assert!(solution.verify(&puzzle, &goal));
+        assert!(!solution.verify(&goal, &puzzle));
+    }
+
+    let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+    let mut solution = agent.uniform_cost_search(SearchStrategy::AStar, Heuristic::OrthoDistance, 1_000_000).unwrap();
+    solution.actions[3] = ActionType::None;
+    assert!(!solution.verify(&puzzle, &goal));
+}
+
+#[test]
+fn legal_moves() {
+    let dimension = Vector2::new(3, 3);
+    let goal = Puzzle::goal(dimension);
+    // The blank starts in the bottom right corner.
+    assert_eq!(goal.legal_actions(), vec![ActionType::Up, ActionType::Left]);
+    let center = goal.act(ActionType::Up).act(ActionType::Left);
+    assert_eq!(center.legal_actions().len(), 4);
+
+    let successors = center.successors(ActionType::Left);
+    assert_eq!(successors.len(), 3);
+    assert!(successors.iter().all(|(action, state)| *action != ActionType::Right && *state != center));
+    assert_eq!(center.successors(ActionType::None).len(), 4);
+    assert_eq!(ActionType::Up.inverse(), ActionType::Down);
+}
+
+#[test]
+fn packed_boards() {
+    for dimension in [Vector2::new(3, 3), Vector2::new(4, 4), Vector2::new(5, 5), Vector2::new(2, 3)] {
+        let puzzle = Puzzle::goal(dimension).act(ActionType::Up).act(ActionType::Left);
+        let packed = puzzle.pack();
+        let cells = (dimension.x * dimension.y) as usize;
+        assert_eq!(matches!(packed, PackedBoard::Nibbles(_)), cells <= 16);
+        assert_eq!(packed.tiles(cells), puzzle.tiles());
+        assert_eq!(packed.unpack(dimension), Ok(puzzle));
+    }
+    // Packed boards come from anywhere, so unpacking checks them like any other board.
+    let packed = Puzzle::goal(Vector2::new(3, 3)).pack();
+    assert_eq!(packed.unpack(Vector2::new(5, 5)), Err(PuzzleError::WrongLength { expected: 25, found: 16 }));
+    assert_eq!(PackedBoard::from_tiles(&[1, 2, 3]).unpack(Vector2::new(1, 3)), Err(PuzzleError::MissingBlank));
+    assert_eq!(PackedBoard::from_tiles(&[1, 1, 0]).unpack(Vector2::new(1, 3)), Err(PuzzleError::DuplicateTile(1)));
+    assert_ne!(Puzzle::goal(Vector2::new(3, 3)).pack(), Puzzle::goal(Vector2::new(3, 3)).act(ActionType::Up).pack());
+}
+
+#[test]
+fn ranking() {
+    let dimension = Vector2::new(2, 2);
+    assert_eq!(Puzzle::orderings(dimension), Some(24));
+    assert_eq!(Puzzle::from_vec(dimension, vec![0, 1, 2, 3]).rank(), Some(0));
+    assert_eq!(Puzzle::goal(dimension).rank(), Some(9));
+    assert_eq!(Puzzle::from_vec(dimension, vec![3, 2, 1, 0]).rank(), Some(23));
+    assert_eq!(Puzzle::from_rank(dimension, 24), Err(PuzzleError::RankOutOfRange(24)));
+
+    // Every 3x3 rank maps back to itself and distinct ranks give distinct boards.
+    let dimension = Vector2::new(3, 3);
+    let mut seen = std::collections::HashSet::new();
+    for rank in (0..362_880).step_by(97) {
+        let puzzle = Puzzle::from_rank(dimension, rank).unwrap();
+        assert_eq!(puzzle.rank(), Some(rank));
+        assert!(seen.insert(puzzle));
+    }
+
+    let large = Puzzle::goal(Vector2::new(4, 4));
+    assert_eq!(Puzzle::from_rank(large.dimension(), large.rank().unwrap()), Ok(large));
+    assert_eq!(Puzzle::orderings(Vector2::new(5, 7)), None);
+    assert_eq!(Puzzle::goal(Vector2::new(6, 6)).rank(), None);
+}
+
+#[test]
+fn large_boards() {
+    // 400 cells, more than a byte can number.
+    let dimension = Vector2::new(20, 20);
+    let goal = Puzzle::goal(dimension);
+    assert_eq!(goal.tiles().iter().max(), Some(&399));
+    let mut tiles = goal.tiles();
+    tiles.sort();
+    assert_eq!(tiles, (0..400).collect::<Vec<Tile>>());
+    let mut random = Puzzle::new(dimension).tiles();
+    random.sort();
+    assert_eq!(random, tiles);
+    assert_eq!(Puzzle::try_from_vec(dimension, (1..=400).collect()), Err(PuzzleError::MissingBlank));
+
+    let puzzle = goal.act(ActionType::Up).act(ActionType::Left).act(ActionType::Up);
+    assert_eq!(puzzle.heuristic_misplaced(&goal), 3);
+    assert_eq!(puzzle.heuristic_distances(&goal), 3);
+    assert_eq!(puzzle.heuristic_linear_conflict(&goal), 3);
+    assert!(puzzle.is_solvable_to(&goal));
+    assert!(matches!(puzzle.pack(), PackedBoard::Wide(_)));
+    assert_eq!(puzzle.pack().unpack(dimension), Ok(puzzle.clone()));
+
+    // Every tile is padded to three characters.
+    let text = goal.to_string();
+    let first = text.lines().next().unwrap();
+    assert!(first.starts_with("  1   2   3 "));
+    assert!(text.lines().last().unwrap().ends_with("399   _ "));
+
+    let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+    let solution = agent.uniform_cost_search(SearchStrategy::AStar, Heuristic::OrthoDistance, 1_000).unwrap();
+    assert_eq!(solution.steps, 3);
+    assert!(solution.verify(&puzzle, &goal));
+}
+
+#[test]
+fn rectangular_boards() {
+    // Moves on a 2x3 board: the blank starts in the bottom right corner.
+    let dimension = Vector2::new(2, 3);
+    let goal = Puzzle::goal(dimension);
+    assert_eq!(goal.blank(), Vector2::new(1, 2));
+    assert_eq!(goal.act(ActionType::Up).blank(), Vector2::new(0, 2));
+    assert_eq!(goal.act(ActionType::Left).blank(), Vector2::new(1, 1));
+    assert_eq!(goal.act(ActionType::Down), goal);
+    assert_eq!(goal.act(ActionType::Right), goal);
+    assert_eq!(goal.to_string(), "1 2 3 \n4 5 _ \n");
+
+    // Exactly half of the 2x3 layouts can reach the goal, and they are the ones found by
+    // sliding from it.
+    let mut reached = std::collections::HashSet::from([goal.clone()]);
+    let mut queue = vec![goal.clone()];
+    while let Some(state) = queue.pop() {
+        for (_, next) in state.successors(ActionType::None) {
+            if reached.insert(next.clone()) {
+                queue.push(next);
+            }
+        }
+    }
+    assert_eq!(reached.len(), 360);
+    for rank in 0..720 {
+        let puzzle = Puzzle::from_rank(dimension, rank).unwrap();
+        assert_eq!(puzzle.is_solvable_to(&goal), reached.contains(&puzzle));
+    }
+
+    for (rows, columns) in [(3, 5), (4, 6), (5, 3)] {
+        let dimension = Vector2::new(rows, columns);
+        let goal = Puzzle::goal(dimension);
+        let puzzle = goal.act(ActionType::Up).act(ActionType::Up).act(ActionType::Left).act(ActionType::Left).act(ActionType::Down);
+        assert_eq!(puzzle.heuristic_misplaced(&goal), 5);
+        assert_eq!(puzzle.heuristic_distances(&goal), 5);
+        assert!(puzzle.heuristic_linear_conflict(&goal) >= 5);
+        assert!(puzzle.is_solvable_to(&goal));
+
+        // Swapping two tiles flips the permutation parity without moving the blank.
+        let mut tiles = puzzle.tiles();
+        tiles.swap(0, 1);
+        assert!(!Puzzle::from_vec(dimension, tiles).is_solvable_to(&goal));
+
+        // One line per row, one entry per column, padded to two characters.
+        let text = puzzle.to_string();
+        assert_eq!(text.lines().count(), rows as usize);
+        assert!(text.lines().all(|line| line.len() == 3 * columns as usize));
+
+        let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+        let solution = agent.solve_optimal(Heuristic::LinearConflict, 100_000).unwrap();
+        assert_eq!(solution.steps, 5);
+        assert!(solution.verify(&puzzle, &goal));
+    }
+}
+
+#[test]
+fn expansions() {
+    // Find the 2x3 layout furthest from the goal; the whole space is 360 states.
+    let dimension = Vector2::new(2, 3);
+    let goal = Puzzle::goal(dimension);
+    let mut layer = vec![goal.clone()];
+    let mut reached = std::collections::HashSet::from([goal.clone()]);
+    let mut furthest = (0, goal.clone());
+    while !layer.is_empty() {
+        let next: Vec<Puzzle> = layer.iter()
+            .flat_map(|state| state.successors(ActionType::None))
+            .map(|(_, state)| state)
+            .filter(|state| reached.insert(state.clone()))
+            .collect();
+        if let Some(state) = next.first() {
+            furthest = (furthest.0 + 1, state.clone());
+        }
+        layer = next;
+    }
+    let (depth, puzzle) = furthest;
+    assert_eq!(depth, 21);
+
+    // Each state is expanded at most once, even by an uninformed search.
+    for strategy in [SearchStrategy::UniformCost, SearchStrategy::AStar, SearchStrategy::BestFirst] {
+        let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+        let solution = agent.uniform_cost_search(strategy, Heuristic::OrthoDistance, 1_000_000).unwrap();
+        assert!(agent.stats().expanded < 360);
+        if strategy != SearchStrategy::BestFirst {
+            assert_eq!(solution.steps, depth);
+        }
+    }
+
+    let puzzle = Puzzle::from_vec(Vector2::new(3, 3), vec![5, 4, 1, 7, 3, 2, 0, 8, 6]);
+    let goal = Puzzle::goal(Vector2::new(3, 3));
+    let mut expanded = Vec::new();
+    for strategy in [SearchStrategy::UniformCost, SearchStrategy::AStar] {
+        let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+        let solution = agent.uniform_cost_search(strategy, Heuristic::OrthoDistance, 1_000_000).unwrap();
+        assert_eq!(solution.steps, 12);
+        expanded.push(agent.stats().expanded);
+    }
+    // Known counts for this board; a search that re-expands explored states goes far higher.
+    assert_eq!(expanded, vec![1441, 18]);
+
+    // The goal itself costs nothing to expand.
+    let mut agent = Agent::new(goal.clone(), goal.clone()).unwrap();
+    agent.uniform_cost_search(SearchStrategy::AStar, Heuristic::OrthoDistance, 1).unwrap();
+    assert_eq!(agent.stats().expanded, 0);
+}
+
+#[test]
+fn search_stats() {
+    let puzzle = Puzzle::from_vec(Vector2::new(3, 3), vec![5, 4, 1, 7, 3, 2, 0, 8, 6]);
+    let goal = Puzzle::goal(Vector2::new(3, 3));
+
+    let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+    let solution = agent.uniform_cost_search(SearchStrategy::AStar, Heuristic::OrthoDistance, 1_000_000).unwrap();
+    let stats = solution.stats;
+    assert_eq!(stats, agent.stats());
+    assert_eq!((stats.expanded, stats.generated, stats.duplicates, stats.reopened), (18, 31, 0, 0));
+    assert_eq!(stats.heuristic_evaluations, 31);
+    assert_eq!(stats.peak_explored, 18);
+    assert!(stats.peak_frontier > 0 && stats.peak_frontier <= 31);
+    // 2 + 4 + 8 + 16 + 32 = 62 nodes in a tree of depth 5 with branching factor 2.
+    let tree = SearchStats { generated: 62, ..stats };
+    assert!((tree.effective_branching_factor(5) - 2.0).abs() < 1e-6);
+
+    // Uninformed searches never ask for the heuristic; IDA* only keeps its current path.
+    let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+    let solution = agent.uniform_cost_search(SearchStrategy::BreadthFirst, Heuristic::OrthoDistance, 1_000_000).unwrap();
+    assert_eq!(solution.stats.heuristic_evaluations, 0);
+    assert!(solution.stats.duplicates > 0);
+    let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+    let solution = agent.uniform_cost_search(SearchStrategy::IDAStar, Heuristic::OrthoDistance, 1_000_000).unwrap();
+    assert!(solution.stats.peak_frontier <= 13);
+    assert_eq!(solution.stats.peak_explored, 0);
+
+    // Counters are still there when the search fails.
+    let mut agent = Agent::new(puzzle, goal).unwrap();
+    let result = agent.uniform_cost_search(SearchStrategy::UniformCost, Heuristic::OrthoDistance, 100);
+    assert_eq!(result.err(), Some(SearchError::BudgetExhausted));
+    assert_eq!(agent.stats().expanded, 100);
+    assert!(agent.stats().generated >= 100);
+}
+
+#[derive(Default)]
+struct Events {
+    started: u32,
+    expanded: u32,
+    generated: u32,
+    goals: u32,
+    exhausted: u32,
+    finished: u32,
+}
+
+struct Recorder(std::rc::Rc<std::cell::RefCell<Events>>);
+impl SearchObserver for Recorder {
+    fn on_start(&mut self, _initial: &Puzzle) { self.0.borrow_mut().started += 1; }
+    fn on_expand(&mut self, _state: &Puzzle, _g: u32) { self.0.borrow_mut().expanded += 1; }
+    fn on_generate(&mut self, _state: &Puzzle, _g: u32) { self.0.borrow_mut().generated += 1; }
+    fn on_goal(&mut self, _solution: &Solution) { self.0.borrow_mut().goals += 1; }
+    fn on_budget_exhausted(&mut self, _stats: &SearchStats) { self.0.borrow_mut().exhausted += 1; }
+    fn on_finish(&mut self, _stats: &SearchStats) { self.0.borrow_mut().finished += 1; }
+}
+
+#[test]
+fn observer() {
+    let puzzle = Puzzle::from_vec(Vector2::new(3, 3), vec![5, 4, 1, 7, 3, 2, 0, 8, 6]);
+    let goal = Puzzle::goal(Vector2::new(3, 3));
+
+    for strategy in [SearchStrategy::AStar, SearchStrategy::IDAStar, SearchStrategy::BreadthFirst, SearchStrategy::BidirectionalMM] {
+        let events = std::rc::Rc::new(std::cell::RefCell::new(Events::default()));
+        let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+        agent.set_observer(Box::new(Recorder(events.clone())));
+        let solution = agent.uniform_cost_search(strategy, Heuristic::OrthoDistance, 1_000_000).unwrap();
+
+        let events = events.borrow();
+        assert_eq!((events.started, events.goals, events.exhausted, events.finished), (1, 1, 0, 1));
+        assert_eq!(events.expanded, solution.stats.expanded);
+        assert_eq!(events.generated, solution.stats.generated);
+    }
+
+    let events = std::rc::Rc::new(std::cell::RefCell::new(Events::default()));
+    let mut agent = Agent::new(puzzle, goal).unwrap();
+    agent.set_observer(Box::new(Recorder(events.clone())));
+    assert!(agent.uniform_cost_search(SearchStrategy::UniformCost, Heuristic::OrthoDistance, 10).is_err());
+    let events = events.borrow();
+    assert_eq!((events.expanded, events.goals, events.exhausted, events.finished), (10, 0, 1, 1));
+}
+
+#[test]
+fn search_limits() {
+    use std::{sync::{Arc, atomic::{AtomicBool, Ordering}}, time::Duration};
+    // Far too hard for uniform-cost search to finish quickly.
+    let puzzle = Puzzle::from_vec(Vector2::new(4, 4), vec![0, 12, 9, 13, 15, 11, 10, 14, 3, 7, 2, 5, 4, 8, 6, 1]);
+    let goal = Puzzle::goal(Vector2::new(4, 4));
+    let run = |strategy, limits| {
+        let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+        agent.set_limits(limits);
+        let result = agent.uniform_cost_search(strategy, Heuristic::OrthoDistance, u32::MAX);
+        (result.err(), agent)
+    };
+
+    let limits = SearchLimits { time: Some(Duration::from_millis(20)), ..SearchLimits::default() };
+    let (error, agent) = run(SearchStrategy::UniformCost, limits);
+    assert_eq!(error, Some(SearchError::TimeLimit));
+    let partial = agent.best_partial().unwrap();
+    assert!(partial.steps > 0);
+    assert!(partial.verify(&puzzle, partial.state_path.last().unwrap()));
+
+    let limits = SearchLimits { max_nodes: Some(500), ..SearchLimits::default() };
+    let (error, agent) = run(SearchStrategy::BreadthFirst, limits);
+    assert_eq!(error, Some(SearchError::MemoryLimit));
+    assert!(agent.stats().peak_explored <= 503);
+
+    // Cancelled from another thread part way through, IDA* reports the branch it was on.
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = cancel.clone();
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(20));
+        flag.store(true, Ordering::Relaxed);
+    });
+    let limits = SearchLimits { cancel: Some(cancel), ..SearchLimits::default() };
+    let (error, agent) = run(SearchStrategy::IDAStar, limits);
+    canceller.join().unwrap();
+    assert_eq!(error, Some(SearchError::Cancelled));
+    let partial = agent.best_partial().unwrap();
+    assert!(partial.state_path.last().unwrap().heuristic_distances(&goal) <= puzzle.heuristic_distances(&goal));
+
+    // Limits don't get in the way of searches that finish in time.
+    let easy = Puzzle::from_vec(Vector2::new(3, 3), vec![5, 4, 1, 7, 3, 2, 0, 8, 6]);
+    let mut agent = Agent::new(easy, Puzzle::goal(Vector2::new(3, 3))).unwrap();
+    agent.set_limits(SearchLimits { time: Some(Duration::from_secs(60)), max_nodes: Some(10_000), cancel: None });
+    assert_eq!(agent.uniform_cost_search(SearchStrategy::AStar, Heuristic::OrthoDistance, 1_000_000).unwrap().steps, 12);
+    assert!(agent.best_partial().is_none());
+}
+
+#[test]
+fn resumable_search() {
+    let puzzle = Puzzle::from_vec(Vector2::new(3, 3), vec![4, 1, 8, 7, 0, 5, 3, 6, 2]);
+    let goal = Puzzle::goal(Vector2::new(3, 3));
+
+    for strategy in [SearchStrategy::AStar, SearchStrategy::BreadthFirst] {
+        let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+        let whole = agent.uniform_cost_search(strategy, Heuristic::OrthoDistance, u32::MAX).unwrap();
+
+        // The same search in small slices ends the same way, having done the same work.
+        let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+        let mut calls = 0;
+        let solution = loop {
+            calls += 1;
+            match agent.uniform_cost_search(strategy, Heuristic::OrthoDistance, 100) {
+                Ok(solution) => break solution,
+                Err(error) => assert_eq!(error, SearchError::BudgetExhausted),
+            }
+        };
+        assert!(calls > 1);
+        assert_eq!(solution.steps, 20);
+        assert_eq!(solution.moves(), whole.moves());
+        assert_eq!(solution.stats.expanded, whole.stats.expanded);
+    }
+
+    // A slice smaller than one IDA* pass starts over every time instead of claiming progress.
+    let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+    for _ in 0..3 {
+        assert_eq!(agent.uniform_cost_search(SearchStrategy::IDAStar, Heuristic::OrthoDistance, 50).err(),
+            Some(SearchError::BudgetExhausted));
+        assert_eq!(agent.stats().expanded, 50);
+    }
+    assert_eq!(agent.uniform_cost_search(SearchStrategy::IDAStar, Heuristic::OrthoDistance, u32::MAX).unwrap().steps, 20);
+
+    // Another strategy starts over.
+    let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+    assert!(agent.uniform_cost_search(SearchStrategy::AStar, Heuristic::OrthoDistance, 100).is_err());
+    assert!(agent.uniform_cost_search(SearchStrategy::UniformCost, Heuristic::OrthoDistance, 10).is_err());
+    assert_eq!(agent.stats().expanded, 10);
+}
+
+#[test]
+fn snapshots() {
+    let puzzle = Puzzle::from_vec(Vector2::new(3, 3), vec![4, 1, 8, 7, 0, 5, 3, 6, 2]);
+    let goal = Puzzle::goal(Vector2::new(3, 3));
+    let path = std::env::temp_dir().join(format!("sliding-puzzle-test-{}.agent", std::process::id()));
+
+    let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+    let whole = agent.solve_optimal(Heuristic::LinearConflict, u32::MAX).unwrap();
+
+    for strategy in [SearchStrategy::AStar, SearchStrategy::BreadthFirst] {
+        let mut agent = Agent::new(puzzle.clone(), goal.clone()).unwrap();
+        assert!(agent.uniform_cost_search(strategy, Heuristic::LinearConflict, 200).is_err());
+        agent.save(&path).unwrap();
+        let before = agent.stats();
+
+        // A fresh agent loaded from disk carries on where the first one stopped.
+        let mut agent = Agent::load(&path).unwrap();
+        assert_eq!(agent.stats().expanded, before.expanded);
+        assert_eq!(agent.stats().generated, before.generated);
+        let solution = agent.uniform_cost_search(strategy, Heuristic::LinearConflict, u32::MAX).unwrap();
+        assert_eq!(solution.steps, whole.steps);
+        assert!(solution.verify(&puzzle, &goal));
+        assert!(solution.stats.expanded > before.expanded);
+    }
+
+    std::fs::write(&path, b"SAGT\x01\x03\x00").unwrap();
+    assert!(matches!(Agent::load(&path), Err(SnapshotError::Corrupt(_))));
+    std::fs::write(&path, b"SPDB").unwrap();
+    assert!(matches!(Agent::load(&path), Err(SnapshotError::Corrupt(_))));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -1,6 +1,8 @@
 use std::ops;
 
 
+/// A grid position or size. `x` is the row, counted down from the top, and `y`
+/// is the column, so a board's dimension is rows by columns.
 #[derive(Clone, Copy, PartialEq, Hash, Debug)]
 pub struct Vector2 {
     pub x: i32, pub y: i32
@@ -14,7 +16,7 @@ impl Vector2 {
         ndarray::Dim((self.x as usize, self.y as usize))
     }
     pub fn distance_ortho(&self, other: &Vector2) -> u32 {
-        (self.x - other.x).abs() as u32 + (self.y - other.y).abs() as u32
+        (self.x - other.x).unsigned_abs() + (self.y - other.y).unsigned_abs()
     }
     pub fn index(&self) -> (usize, usize) {
         (self.x as usize, self.y as usize)